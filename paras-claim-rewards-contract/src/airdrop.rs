@@ -0,0 +1,197 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Merkle-root airdrop funded from the deposit.
+/// Leaves are `sha256(borsh(index: u64, account_id: String, amount: u128))`,
+/// nodes are the sha256 of their two children concatenated in ascending order.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Airdrop {
+    token_id: AccountId,
+    merkle_root: Vec<u8>,
+    total_amount: u128,
+    claimed_amount: u128,
+    ends_at: u64,
+    is_closed: bool,
+    /// One bit per leaf index, 64 leaves per word.
+    claimed_bitmap: LookupMap<u64, u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WrappedAirdrop {
+    token_id: AccountId,
+    merkle_root: Base64VecU8,
+    total_amount: U128,
+    claimed_amount: U128,
+    ends_at: U64,
+    is_closed: bool,
+}
+
+impl Airdrop {
+    pub fn is_claimed(&self, index: u64) -> bool {
+        let word = self.claimed_bitmap.get(&(index / 64)).unwrap_or(0);
+        word & (1 << (index % 64)) != 0
+    }
+
+    fn set_claimed(&mut self, index: u64, is_claimed: bool) {
+        let word = self.claimed_bitmap.get(&(index / 64)).unwrap_or(0);
+        let word = if is_claimed { word | (1 << (index % 64)) } else { word & !(1 << (index % 64)) };
+        self.claimed_bitmap.insert(&(index / 64), &word);
+    }
+
+    pub fn to_wairdrop(&self) -> WrappedAirdrop {
+        WrappedAirdrop {
+            token_id: self.token_id.clone(),
+            merkle_root: self.merkle_root.clone().into(),
+            total_amount: self.total_amount.into(),
+            claimed_amount: self.claimed_amount.into(),
+            ends_at: self.ends_at.into(),
+            is_closed: self.is_closed,
+        }
+    }
+}
+
+pub fn airdrop_leaf(index: u64, account_id: &AccountId, amount: u128) -> Vec<u8> {
+    env::sha256(&(index, account_id.clone(), amount).try_to_vec().unwrap())
+}
+
+fn verify_proof(merkle_root: &[u8], leaf: Vec<u8>, proof: &[Base64VecU8]) -> bool {
+    let computed_root = proof.iter().fold(leaf, |hash, node| {
+        if hash <= node.0 {
+            env::sha256(&[hash, node.0.clone()].concat())
+        } else {
+            env::sha256(&[node.0.clone(), hash].concat())
+        }
+    });
+    computed_root == merkle_root
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Funds a new airdrop with `total_amount` from the deposit. Returns the airdrop id.
+    #[payable]
+    pub fn create_airdrop(
+        &mut self,
+        merkle_root: Base64VecU8,
+        total_amount: U128,
+        ends_at: U64,
+        token_id: Option<ValidAccountId>,
+    ) -> u64 {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        assert_eq!(merkle_root.0.len(), 32, "ERR_INVALID_MERKLE_ROOT");
+        assert!(u64::from(ends_at) > env::block_timestamp(), "ERR_INVALID_ENDS_AT");
+        let token_id = self.internal_whitelisted_token_id(token_id);
+        let initial_storage_usage = env::storage_usage();
+        self.internal_withdraw_deposit(&token_id, total_amount.into());
+        self.internal_update_stats(&token_id, |stats| stats.push(total_amount.into()));
+
+        let airdrop_id = self.next_airdrop_id;
+        let mut prefix = b"b".to_vec();
+        prefix.extend(airdrop_id.to_le_bytes().iter());
+        self.airdrops.insert(&airdrop_id, &Airdrop {
+            token_id,
+            merkle_root: merkle_root.0,
+            total_amount: total_amount.into(),
+            claimed_amount: 0,
+            ends_at: ends_at.into(),
+            is_closed: false,
+            claimed_bitmap: LookupMap::new(prefix),
+        });
+        self.next_airdrop_id += 1;
+        self.internal_charge_storage(None, initial_storage_usage);
+        airdrop_id
+    }
+
+    /// Claims the `amount` of leaf `index` of an airdrop, proving it with the sibling hashes from the leaf up.
+    #[payable]
+    pub fn claim_with_proof(&mut self, airdrop_id: u64, index: u64, amount: U128, proof: Vec<Base64VecU8>) -> Promise {
+        assert_one_yocto();
+        self.assert_not_paused(Operation::Claim);
+        let account_id = env::predecessor_account_id();
+        let mut airdrop = self.airdrops.get(&airdrop_id).expect("ERR_NO_AIRDROP");
+        assert!(!airdrop.is_closed && env::block_timestamp() < airdrop.ends_at, "ERR_AIRDROP_ENDED");
+        assert!(!airdrop.is_claimed(index), "ERR_ALREADY_CLAIMED");
+        assert!(
+            verify_proof(&airdrop.merkle_root, airdrop_leaf(index, &account_id, amount.into()), &proof),
+            "ERR_INVALID_PROOF"
+        );
+        airdrop.claimed_amount = airdrop.claimed_amount.checked_add(amount.into()).expect("ERR_INTEGER_OVERFLOW");
+        assert!(airdrop.claimed_amount <= airdrop.total_amount, "ERR_AIRDROP_AMOUNT_EXCEEDED");
+        airdrop.set_claimed(index, true);
+        self.airdrops.insert(&airdrop_id, &airdrop);
+        self.internal_update_stats(&airdrop.token_id, |stats| stats.claim(amount.into()));
+
+        events::emit_claim_reward(&account_id, &airdrop.token_id, amount.into());
+
+        ext_fungible_token::ft_transfer(
+            account_id.clone(),
+            amount,
+            None,
+            &airdrop.token_id,
+            1,
+            GAS_FOR_FT_TRANSFER
+        ).then(ext_self::on_airdrop_claim_resolved(
+            account_id,
+            airdrop_id,
+            index,
+            amount,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ))
+    }
+
+    /// Unmarks the leaf if `ft_transfer` failed, so it can be claimed again.
+    /// Returns the amount that was actually transferred.
+    #[private]
+    pub fn on_airdrop_claim_resolved(&mut self, account_id: AccountId, airdrop_id: u64, index: u64, amount: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        let mut airdrop = self.airdrops.get(&airdrop_id).expect("ERR_NO_AIRDROP");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit_claim_reward_resolved(&account_id, &airdrop.token_id, amount.into(), true);
+                amount
+            }
+            PromiseResult::Failed => {
+                airdrop.claimed_amount -= u128::from(amount);
+                self.internal_update_stats(&airdrop.token_id, |stats| stats.refund_claim(amount.into()));
+                if airdrop.is_closed {
+                    // the unclaimed amount already went back to the deposit
+                    self.internal_deposit(&airdrop.token_id, amount.into());
+                    self.internal_update_stats(&airdrop.token_id, |stats| stats.release(amount.into()));
+                } else {
+                    airdrop.set_claimed(index, false);
+                }
+                self.airdrops.insert(&airdrop_id, &airdrop);
+                events::emit_claim_reward_resolved(&account_id, &airdrop.token_id, amount.into(), false);
+                U128(0)
+            }
+        }
+    }
+
+    /// Returns the unclaimed amount of an ended airdrop to the deposit.
+    #[payable]
+    pub fn close_airdrop(&mut self, airdrop_id: u64) -> U128 {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let mut airdrop = self.airdrops.get(&airdrop_id).expect("ERR_NO_AIRDROP");
+        assert!(!airdrop.is_closed, "ERR_AIRDROP_CLOSED");
+        assert!(env::block_timestamp() >= airdrop.ends_at, "ERR_AIRDROP_NOT_ENDED");
+        let unclaimed_amount = airdrop.total_amount - airdrop.claimed_amount;
+        airdrop.is_closed = true;
+        self.airdrops.insert(&airdrop_id, &airdrop);
+        self.internal_deposit(&airdrop.token_id, unclaimed_amount);
+        self.internal_update_stats(&airdrop.token_id, |stats| stats.release(unclaimed_amount));
+        unclaimed_amount.into()
+    }
+
+    pub fn get_airdrop(&self, airdrop_id: u64) -> Option<WrappedAirdrop> {
+        self.airdrops.get(&airdrop_id).map(|airdrop| airdrop.to_wairdrop())
+    }
+
+    pub fn is_airdrop_claimed(&self, airdrop_id: u64, index: u64) -> bool {
+        self.airdrops.get(&airdrop_id).expect("ERR_NO_AIRDROP").is_claimed(index)
+    }
+}
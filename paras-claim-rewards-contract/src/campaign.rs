@@ -0,0 +1,173 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Named reward program with its own budget, funded through `ft_on_transfer`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Campaign {
+    name: String,
+    token_id: AccountId,
+    /// Total amount funded.
+    budget: u128,
+    /// Total amount pushed.
+    spent_amount: u128,
+    num_rewards: u64,
+    starts_at: u64,
+    ends_at: u64,
+    /// Accounts allowed to push from the budget, besides the owner.
+    pushers: Vec<AccountId>,
+    /// The remaining budget went back to the deposit.
+    is_closed: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WrappedCampaign {
+    campaign_id: u64,
+    name: String,
+    token_id: AccountId,
+    budget: U128,
+    spent_amount: U128,
+    remaining_budget: U128,
+    num_rewards: u64,
+    starts_at: U64,
+    ends_at: U64,
+    pushers: Vec<AccountId>,
+    is_closed: bool,
+}
+
+impl Campaign {
+    fn remaining_budget(&self) -> u128 {
+        if self.is_closed { 0 } else { self.budget - self.spent_amount }
+    }
+
+    fn to_wcampaign(&self, campaign_id: u64) -> WrappedCampaign {
+        WrappedCampaign {
+            campaign_id,
+            name: self.name.clone(),
+            token_id: self.token_id.clone(),
+            budget: self.budget.into(),
+            spent_amount: self.spent_amount.into(),
+            remaining_budget: self.remaining_budget().into(),
+            num_rewards: self.num_rewards,
+            starts_at: self.starts_at.into(),
+            ends_at: self.ends_at.into(),
+            pushers: self.pushers.clone(),
+            is_closed: self.is_closed,
+        }
+    }
+}
+
+impl Contract {
+    /// Adds `amount` of `token_id` received through `ft_on_transfer` to the budget of a campaign.
+    pub(crate) fn internal_fund_campaign(&mut self, campaign_id: u64, token_id: &AccountId, amount: u128) {
+        let mut campaign = self.campaigns.get(&campaign_id).expect("ERR_NO_CAMPAIGN");
+        assert_eq!(&campaign.token_id, token_id, "ERR_CAMPAIGN_TOKEN_MISMATCH");
+        assert!(!campaign.is_closed && env::block_timestamp() < campaign.ends_at, "ERR_CAMPAIGN_ENDED");
+        campaign.budget = campaign.budget.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+        self.campaigns.insert(&campaign_id, &campaign);
+        self.internal_update_stats(token_id, |stats| stats.add_campaign_budget(amount));
+    }
+
+    /// Takes `amount` out of the remaining budget of a running campaign, on behalf of the caller.
+    pub(crate) fn internal_use_campaign_budget(&mut self, campaign_id: u64, token_id: &AccountId, amount: u128) {
+        let mut campaign = self.campaigns.get(&campaign_id).expect("ERR_NO_CAMPAIGN");
+        let pusher_id = env::predecessor_account_id();
+        assert!(pusher_id == self.owner || campaign.pushers.contains(&pusher_id), "ERR_NOT_CAMPAIGN_PUSHER");
+        assert_eq!(&campaign.token_id, token_id, "ERR_CAMPAIGN_TOKEN_MISMATCH");
+        let now = env::block_timestamp();
+        assert!(!campaign.is_closed && campaign.starts_at <= now && now < campaign.ends_at, "ERR_CAMPAIGN_NOT_ACTIVE");
+        assert!(amount <= campaign.remaining_budget(), "ERR_CAMPAIGN_BUDGET_NOT_ENOUGH");
+        campaign.spent_amount += amount;
+        campaign.num_rewards += 1;
+        self.campaigns.insert(&campaign_id, &campaign);
+        self.internal_update_stats(token_id, |stats| stats.remove_campaign_budget(amount));
+    }
+
+    /// Gives back the revoked or expired `amount` of a campaign reward to the campaign budget,
+    /// or to the deposit once the campaign is closed.
+    pub(crate) fn internal_return_campaign_budget(&mut self, campaign_id: u64, token_id: &AccountId, amount: u128) {
+        match self.campaigns.get(&campaign_id) {
+            Some(mut campaign) if !campaign.is_closed => {
+                campaign.spent_amount = campaign.spent_amount.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW");
+                self.campaigns.insert(&campaign_id, &campaign);
+                self.internal_update_stats(token_id, |stats| stats.add_campaign_budget(amount));
+            }
+            _ => self.internal_deposit(token_id, amount),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Creates an empty campaign, funded afterwards with `ft_transfer_call` and a `campaign` msg.
+    /// Returns the campaign id.
+    #[payable]
+    pub fn create_campaign(
+        &mut self,
+        name: String,
+        token_id: Option<ValidAccountId>,
+        starts_at: U64,
+        ends_at: U64,
+        pushers: Vec<ValidAccountId>,
+    ) -> u64 {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        assert!(u64::from(starts_at) < u64::from(ends_at), "ERR_INVALID_CAMPAIGN_PERIOD");
+        let token_id = self.internal_whitelisted_token_id(token_id);
+        let initial_storage_usage = env::storage_usage();
+
+        let campaign_id = self.next_campaign_id;
+        self.campaigns.insert(&campaign_id, &Campaign {
+            name,
+            token_id,
+            budget: 0,
+            spent_amount: 0,
+            num_rewards: 0,
+            starts_at: starts_at.into(),
+            ends_at: ends_at.into(),
+            pushers: pushers.into_iter().map(|pusher| pusher.into()).collect(),
+            is_closed: false,
+        });
+        self.next_campaign_id += 1;
+        self.internal_charge_storage(None, initial_storage_usage);
+        campaign_id
+    }
+
+    /// Replaces the accounts allowed to push from the campaign budget.
+    #[payable]
+    pub fn set_campaign_pushers(&mut self, campaign_id: u64, pushers: Vec<ValidAccountId>) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        let initial_storage_usage = env::storage_usage();
+        let mut campaign = self.campaigns.get(&campaign_id).expect("ERR_NO_CAMPAIGN");
+        campaign.pushers = pushers.into_iter().map(|pusher| pusher.into()).collect();
+        self.campaigns.insert(&campaign_id, &campaign);
+        self.internal_charge_storage(None, initial_storage_usage);
+    }
+
+    /// Returns the remaining budget of an ended campaign to the deposit.
+    #[payable]
+    pub fn close_campaign(&mut self, campaign_id: u64) -> U128 {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let mut campaign = self.campaigns.get(&campaign_id).expect("ERR_NO_CAMPAIGN");
+        assert!(!campaign.is_closed, "ERR_CAMPAIGN_CLOSED");
+        assert!(env::block_timestamp() >= campaign.ends_at, "ERR_CAMPAIGN_NOT_ENDED");
+        let remaining_budget = campaign.remaining_budget();
+        campaign.is_closed = true;
+        self.campaigns.insert(&campaign_id, &campaign);
+        self.internal_deposit(&campaign.token_id, remaining_budget);
+        self.internal_update_stats(&campaign.token_id, |stats| stats.remove_campaign_budget(remaining_budget));
+        remaining_budget.into()
+    }
+
+    pub fn get_campaign(&self, campaign_id: u64) -> Option<WrappedCampaign> {
+        self.campaigns.get(&campaign_id).map(|campaign| campaign.to_wcampaign(campaign_id))
+    }
+
+    /// Campaigns in creation order.
+    pub fn list_campaigns(&self, from_index: u64, limit: u64) -> Vec<WrappedCampaign> {
+        (from_index..std::cmp::min(from_index.saturating_add(limit), self.next_campaign_id))
+            .filter_map(|campaign_id| self.get_campaign(campaign_id))
+            .collect()
+    }
+}
@@ -0,0 +1,114 @@
+use near_sdk::{log, AccountId};
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::{self, json, Value};
+
+/// NEP-297 event standard name and version of this contract's events.
+pub const EVENT_STANDARD: &str = "paras_claim_rewards";
+pub const EVENT_VERSION: &str = "1.0.0";
+
+fn emit_event(event: &str, data: Value) {
+    let event = json!({
+        "standard": EVENT_STANDARD,
+        "version": EVENT_VERSION,
+        "event": event,
+        "data": [data],
+    });
+    log!("EVENT_JSON:{}", serde_json::to_string(&event).unwrap());
+}
+
+pub fn emit_deposit(token_id: &AccountId, sender_id: &AccountId, amount: u128) {
+    emit_event("deposit", json!({
+        "token_id": token_id,
+        "sender_id": sender_id,
+        "amount": U128(amount),
+    }));
+}
+
+pub fn emit_fund_campaign(campaign_id: u64, token_id: &AccountId, sender_id: &AccountId, amount: u128) {
+    emit_event("fund_campaign", json!({
+        "campaign_id": campaign_id,
+        "token_id": token_id,
+        "sender_id": sender_id,
+        "amount": U128(amount),
+    }));
+}
+
+pub fn emit_funder_deposit(funder_id: &AccountId, token_id: &AccountId, amount: u128) {
+    emit_event("funder_deposit", json!({
+        "funder_id": funder_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+    }));
+}
+
+pub fn emit_funder_withdraw(funder_id: &AccountId, token_id: &AccountId, amount: u128) {
+    emit_event("funder_withdraw", json!({
+        "funder_id": funder_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+    }));
+}
+
+pub fn emit_push_reward(account_id: &AccountId, token_id: &AccountId, amount: u128, memo: &str, reward_index: u64) {
+    emit_event("push_reward", json!({
+        "account_id": account_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+        "memo": memo,
+        "reward_index": reward_index,
+    }));
+}
+
+pub fn emit_claim_reward(account_id: &AccountId, token_id: &AccountId, amount: u128) {
+    emit_event("claim_reward", json!({
+        "account_id": account_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+    }));
+}
+
+pub fn emit_revoke_reward(account_id: &AccountId, token_id: &AccountId, amount: u128, reward_index: u64, reason: &str) {
+    emit_event("revoke_reward", json!({
+        "account_id": account_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+        "reward_index": reward_index,
+        "reason": reason,
+    }));
+}
+
+pub fn emit_revoke_vesting(account_id: &AccountId, token_id: &AccountId, amount: u128, reward_index: u64) {
+    emit_event("revoke_vesting", json!({
+        "account_id": account_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+        "reward_index": reward_index,
+    }));
+}
+
+/// Emitted once per account, token and source the expired amount is returned to.
+pub fn emit_sweep_expired(account_id: &AccountId, token_id: &AccountId, amount: u128) {
+    emit_event("sweep_expired", json!({
+        "account_id": account_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+    }));
+}
+
+pub fn emit_withdraw_deposit(token_id: &AccountId, receiver_id: &AccountId, amount: u128) {
+    emit_event("withdraw_deposit", json!({
+        "token_id": token_id,
+        "receiver_id": receiver_id,
+        "amount": U128(amount),
+    }));
+}
+
+/// Emitted from the transfer callback, `claim_reward_failed` means the amount was re-credited.
+pub fn emit_claim_reward_resolved(account_id: &AccountId, token_id: &AccountId, amount: u128, is_success: bool) {
+    let event = if is_success { "claim_reward_succeeded" } else { "claim_reward_failed" };
+    emit_event(event, json!({
+        "account_id": account_id,
+        "token_id": token_id,
+        "amount": U128(amount),
+    }));
+}
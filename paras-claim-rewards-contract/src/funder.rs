@@ -0,0 +1,118 @@
+use crate::*;
+
+impl Contract {
+    pub(crate) fn internal_funder_balance(&self, funder_id: &AccountId, token_id: &AccountId) -> u128 {
+        self.funder_balances.get(funder_id)
+            .and_then(|balances| balances.get(token_id).cloned())
+            .unwrap_or(0)
+    }
+
+    fn internal_set_funder_balance(&mut self, funder_id: &AccountId, token_id: &AccountId, amount: u128) {
+        let mut balances = self.funder_balances.get(funder_id).unwrap_or_default();
+        if amount == 0 {
+            balances.remove(token_id);
+        } else {
+            balances.insert(token_id.clone(), amount);
+        }
+        if balances.is_empty() {
+            self.funder_balances.remove(funder_id);
+        } else {
+            self.funder_balances.insert(funder_id, &balances);
+        }
+    }
+
+    /// Adds `amount` of `token_id` to the pool of `funder_id`.
+    pub(crate) fn internal_funder_deposit(&mut self, funder_id: &AccountId, token_id: &AccountId, amount: u128) {
+        let balance = self.internal_funder_balance(funder_id, token_id).checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+        self.internal_set_funder_balance(funder_id, token_id, balance);
+        self.internal_update_stats(token_id, |stats| stats.add_funder_balance(amount));
+    }
+
+    /// Takes `amount` of `token_id` out of the pool of `funder_id`.
+    pub(crate) fn internal_funder_withdraw(&mut self, funder_id: &AccountId, token_id: &AccountId, amount: u128) {
+        let balance = self.internal_funder_balance(funder_id, token_id).checked_sub(amount).expect("ERR_FUNDER_BALANCE_NOT_ENOUGH");
+        self.internal_set_funder_balance(funder_id, token_id, balance);
+        self.internal_update_stats(token_id, |stats| stats.remove_funder_balance(amount));
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pushes a reward from the pool of the calling funder.
+    /// The new storage is paid by the attached deposit, then the funder storage balance, never by the owner storage pool.
+    #[payable]
+    pub fn push_funded_reward(
+        &mut self,
+        account_id: ValidAccountId,
+        amount: U128,
+        memo: String,
+        token_id: Option<ValidAccountId>,
+        expires_at: Option<U64>,
+    ) -> U128 {
+        self.assert_funder();
+        self.assert_not_paused(Operation::Push);
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        let token_id = self.internal_whitelisted_token_id(token_id);
+        let funder_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let current_amount = self.internal_push_reward(
+            account_id.as_ref(),
+            &token_id,
+            amount.into(),
+            memo,
+            expires_at.map(|e| e.into()),
+            RewardSource::Funder(funder_id.clone()),
+            &funder_id,
+        );
+        self.internal_charge_account_storage(&funder_id, initial_storage_usage);
+        current_amount.into()
+    }
+
+    /// Sends unused pool balance back to the calling funder. Still allowed after the funder role is revoked.
+    #[payable]
+    pub fn withdraw_funder_balance(&mut self, amount: U128, token_id: Option<ValidAccountId>) -> Promise {
+        assert_one_yocto();
+        let token_id = self.internal_token_id(token_id);
+        let funder_id = env::predecessor_account_id();
+        self.internal_funder_withdraw(&funder_id, &token_id, amount.into());
+
+        events::emit_funder_withdraw(&funder_id, &token_id, amount.into());
+
+        ext_fungible_token::ft_transfer(
+            funder_id.clone(),
+            amount,
+            None,
+            &token_id,
+            1,
+            GAS_FOR_FT_TRANSFER
+        ).then(ext_self::on_funder_withdraw_resolved(
+            funder_id,
+            token_id,
+            amount,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ))
+    }
+
+    /// Restores the funder balance if the withdrawal `ft_transfer` failed.
+    /// Returns the amount that was actually withdrawn.
+    #[private]
+    pub fn on_funder_withdraw_resolved(&mut self, funder_id: AccountId, token_id: AccountId, amount: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => amount,
+            PromiseResult::Failed => {
+                self.internal_funder_deposit(&funder_id, &token_id, amount.into());
+                log!("Withdraw failed, restored funder balance of {} {} {}", funder_id, u128::from(amount), token_id);
+                U128(0)
+            }
+        }
+    }
+
+    pub fn get_funder_balance(&self, account_id: ValidAccountId, token_id: Option<ValidAccountId>) -> U128 {
+        let token_id = self.internal_token_id(token_id);
+        self.internal_funder_balance(account_id.as_ref(), &token_id).into()
+    }
+}
@@ -1,15 +1,37 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, assert_one_yocto, Promise, log};
-use near_sdk::json_types::{ValidAccountId, U128};
-use near_sdk::collections::{LookupMap};
+use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, assert_one_yocto, Promise, PromiseResult, log};
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedSet};
+use std::collections::HashMap;
 
 near_sdk::setup_alloc!();
 
-use crate::utils::{ext_fungible_token, GAS_FOR_FT_TRANSFER};
-use crate::rewards::{Rewards, Reward, WrappedReward};
+use crate::utils::{
+    ext_fungible_token, ext_self, GAS_FOR_FT_TRANSFER, GAS_FOR_FT_TRANSFER_CALL, GAS_FOR_RESOLVE_TRANSFER,
+    GAS_FOR_CLAIM_REWARD_CALL, GAS_FOR_FT_BALANCE_OF, GAS_FOR_PUSH_REWARD, MAX_REWARDS_PER_BATCH,
+};
+use crate::rewards::{Rewards, Reward, RewardSource, ExpiringAmount, Vesting, ClaimStatus, WrappedClaims, WrappedRewards, WrappedVesting};
+use crate::owner::Role;
+use crate::pause::Operation;
+use crate::migration::{RewardsV1, CURRENT_STATE_VERSION};
+use crate::airdrop::Airdrop;
+use crate::stats::Stats;
+use crate::campaign::Campaign;
 mod utils;
 mod rewards;
 mod token_receiver;
+mod storage_impl;
+mod tokens;
+mod owner;
+mod events;
+mod pause;
+mod migration;
+mod upgrade;
+mod airdrop;
+mod voucher;
+mod stats;
+mod campaign;
+mod funder;
 
 /*
     Implementation of claim rewards.
@@ -20,7 +42,29 @@ pub struct Contract {
     owner: AccountId,
     token: AccountId,
     records: LookupMap<AccountId, Rewards>,
-    deposited_amount: u128,
+    /// Records in the V1 layout, moved to `records` when touched.
+    legacy_records: LookupMap<AccountId, RewardsV1>,
+    deposited_amount: LookupMap<AccountId, u128>,
+    storage_deposits: LookupMap<AccountId, u128>,
+    whitelisted_tokens: UnorderedSet<AccountId>,
+    pending_owner: Option<AccountId>,
+    roles: LookupMap<AccountId, Vec<Role>>,
+    paused_operations: Vec<Operation>,
+    airdrops: LookupMap<u64, Airdrop>,
+    next_airdrop_id: u64,
+    /// ed25519 public key vouchers are signed with.
+    voucher_signer: Option<Vec<u8>>,
+    /// Account and nonce of the redeemed vouchers.
+    used_vouchers: LookupSet<(AccountId, u64)>,
+    stats: LookupMap<AccountId, Stats>,
+    num_reward_accounts: u64,
+    next_reward_id: u64,
+    /// Idempotency keys of the applied pushes.
+    applied_keys: LookupSet<String>,
+    campaigns: LookupMap<u64, Campaign>,
+    next_campaign_id: u64,
+    /// Pool balance per token of each funder.
+    funder_balances: LookupMap<AccountId, HashMap<AccountId, u128>>,
 }
 
 #[near_bindgen]
@@ -31,90 +75,605 @@ impl Contract{
         token: ValidAccountId,
     ) -> Self {
         assert!(!env::state_exists(), "ERR_CONTRACT_ALREADY_INTIALIZED");
-        let this = Self {
+        let mut this = Self {
             owner: owner.into(),
-            token: token.into(),
-            records: LookupMap::new(b"t".to_vec()),
-            deposited_amount: 0,
+            token: token.clone().into(),
+            records: LookupMap::new(b"e".to_vec()),
+            legacy_records: LookupMap::new(b"t".to_vec()),
+            deposited_amount: LookupMap::new(b"d".to_vec()),
+            storage_deposits: LookupMap::new(b"s".to_vec()),
+            whitelisted_tokens: UnorderedSet::new(b"w".to_vec()),
+            pending_owner: None,
+            roles: LookupMap::new(b"r".to_vec()),
+            paused_operations: Vec::new(),
+            airdrops: LookupMap::new(b"a".to_vec()),
+            next_airdrop_id: 0,
+            voucher_signer: None,
+            used_vouchers: LookupSet::new(b"n".to_vec()),
+            stats: LookupMap::new(b"x".to_vec()),
+            num_reward_accounts: 0,
+            next_reward_id: 0,
+            applied_keys: LookupSet::new(b"k".to_vec()),
+            campaigns: LookupMap::new(b"c".to_vec()),
+            next_campaign_id: 0,
+            funder_balances: LookupMap::new(b"f".to_vec()),
         };
+        this.whitelisted_tokens.insert(token.as_ref());
+        migration::write_state_version(CURRENT_STATE_VERSION);
         this
     }
 
-    fn internal_deposit(&mut self, amount: u128) {
-        self.deposited_amount = self.deposited_amount.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+    fn internal_deposit(&mut self, token_id: &AccountId, amount: u128) {
+        let deposited_amount = self.internal_deposited_amount(token_id).checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+        self.deposited_amount.insert(token_id, &deposited_amount);
     }
 
-    pub fn get_rewards(&self, from_index: u64, limit: u64, account_id: ValidAccountId) -> Vec<WrappedReward> {
-        let user_rewards = self.records.get(account_id.as_ref()).unwrap();
-        let end_index = user_rewards.get_rewards_len().saturating_sub(from_index);
-        (end_index.saturating_sub(limit)..end_index).rev()
-            .map(|index| user_rewards.get_reward(index).to_wreward())
-            .collect()
+    fn internal_withdraw_deposit(&mut self, token_id: &AccountId, amount: u128) {
+        let deposited_amount = self.internal_deposited_amount(token_id).checked_sub(amount).expect("ERR_DEPOSITED_AMOUNT_NOT_ENOUGH");
+        self.deposited_amount.insert(token_id, &deposited_amount);
+    }
+
+    fn internal_deposited_amount(&self, token_id: &AccountId) -> u128 {
+        self.deposited_amount.get(token_id).unwrap_or(0)
+    }
+
+    /// `token_id` or the default token. Claims, withdrawals and views accept tokens removed from the whitelist.
+    fn internal_token_id(&self, token_id: Option<ValidAccountId>) -> AccountId {
+        token_id.map(|t| t.into()).unwrap_or_else(|| self.token.clone())
+    }
+
+    /// `token_id` or the default token, which has to be whitelisted to be pushed.
+    fn internal_whitelisted_token_id(&self, token_id: Option<ValidAccountId>) -> AccountId {
+        let token_id = self.internal_token_id(token_id);
+        assert!(self.whitelisted_tokens.contains(&token_id), "ERR_TOKEN_NOT_WHITELISTED");
+        token_id
+    }
+
+    /// Rewards of `account_id` created in `[from_timestamp, to_timestamp)`, newest first.
+    /// Empty for an account that never received a reward.
+    pub fn get_rewards(
+        &self,
+        from_index: u64,
+        limit: u64,
+        account_id: ValidAccountId,
+        from_timestamp: Option<U64>,
+        to_timestamp: Option<U64>,
+    ) -> WrappedRewards {
+        let from_timestamp: u64 = from_timestamp.map(|t| t.into()).unwrap_or(0);
+        let to_timestamp: u64 = to_timestamp.map(|t| t.into()).unwrap_or(u64::MAX);
+        match self.internal_view_rewards(account_id.as_ref()) {
+            Some(user_rewards) => {
+                let start_index = user_rewards.internal_index_from(from_timestamp);
+                let end_index = std::cmp::max(start_index, user_rewards.internal_index_from(to_timestamp));
+                WrappedRewards {
+                    rewards: internal_page(start_index, end_index, from_index, limit, |index| {
+                        user_rewards.get_reward(index).to_wreward()
+                    }),
+                    total_count: end_index - start_index,
+                }
+            }
+            None => WrappedRewards { rewards: vec![], total_count: 0 },
+        }
+    }
+
+    /// Claims of `account_id`, newest first.
+    pub fn get_claims(&self, account_id: ValidAccountId, from_index: u64, limit: u64) -> WrappedClaims {
+        match self.records.get(account_id.as_ref()) {
+            Some(user_rewards) => {
+                let total_count = user_rewards.get_claims_len();
+                WrappedClaims {
+                    claims: internal_page(0, total_count, from_index, limit, |index| user_rewards.get_claim(index)),
+                    total_count,
+                }
+            }
+            None => WrappedClaims { claims: vec![], total_count: 0 },
+        }
+    }
+
+    /// Whether a push with `idempotency_key` was applied.
+    pub fn is_key_applied(&self, idempotency_key: String) -> bool {
+        self.applied_keys.contains(&idempotency_key)
+    }
+
+    pub fn has_rewards(&self, account_id: ValidAccountId) -> bool {
+        self.records.contains_key(account_id.as_ref()) || self.legacy_records.contains_key(account_id.as_ref())
     }
 
-    pub fn get_reward_amount(&self, account_id: ValidAccountId) -> U128 {
-        let current_rewards = self.records.get(account_id.as_ref()).unwrap();
-        current_rewards.internal_reward_amount().into()
+    pub fn get_reward_amount(&self, account_id: ValidAccountId, token_id: Option<ValidAccountId>) -> U128 {
+        let token_id = self.internal_token_id(token_id);
+        self.internal_view_rewards(account_id.as_ref())
+            .map(|current_rewards| current_rewards.internal_claimable_amount(&token_id, env::block_timestamp()))
+            .unwrap_or(0)
+            .into()
     }
     
     
     #[payable]
-    pub fn claim_reward(&mut self, amount: U128) -> Promise {
+    pub fn claim_reward(&mut self, amount: U128, token_id: Option<ValidAccountId>) -> Promise {
+        self.internal_claim_reward_to(env::predecessor_account_id(), amount, token_id)
+    }
+
+    /// Claims to `receiver_id` instead of the caller. A failed transfer is re-credited to the caller.
+    #[payable]
+    pub fn claim_reward_to(&mut self, receiver_id: ValidAccountId, amount: U128, token_id: Option<ValidAccountId>) -> Promise {
+        self.internal_claim_reward_to(receiver_id.into(), amount, token_id)
+    }
+
+    fn internal_claim_reward_to(&mut self, receiver_id: AccountId, amount: U128, token_id: Option<ValidAccountId>) -> Promise {
+        assert_one_yocto();
+        self.assert_not_paused(Operation::Claim);
+        let account_id = env::predecessor_account_id();
+        let token_id = self.internal_token_id(token_id);
+        let (claim_index, used_expiring_amounts) = self.internal_claim_reward(&account_id, &token_id, amount.into(), &receiver_id);
+
+        ext_fungible_token::ft_transfer(
+            receiver_id,
+            amount,
+            None,
+            &token_id,
+            1,
+            GAS_FOR_FT_TRANSFER
+        ).then(ext_self::on_claim_resolved(
+            account_id,
+            token_id,
+            amount,
+            claim_index,
+            used_expiring_amounts,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ))
+    }
+
+    /// Claims to `receiver_id` with `ft_transfer_call`, e.g. to stake the reward right away.
+    /// The amount refunded by the receiver is credited back to the caller.
+    #[payable]
+    pub fn claim_reward_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        msg: String,
+        token_id: Option<ValidAccountId>,
+    ) -> Promise {
         assert_one_yocto();
-        let mut current_rewards = self.records.get(&env::predecessor_account_id()).unwrap();
-        let current_amount = current_rewards.internal_reward_amount();
+        self.assert_not_paused(Operation::Claim);
+        assert!(
+            env::prepaid_gas() >= GAS_FOR_FT_TRANSFER_CALL + GAS_FOR_RESOLVE_TRANSFER + GAS_FOR_CLAIM_REWARD_CALL,
+            "ERR_NOT_ENOUGH_GAS"
+        );
+        let account_id = env::predecessor_account_id();
+        let token_id = self.internal_token_id(token_id);
+        let (claim_index, used_expiring_amounts) = self.internal_claim_reward(&account_id, &token_id, amount.into(), receiver_id.as_ref());
+
+        ext_fungible_token::ft_transfer_call(
+            receiver_id.into(),
+            amount,
+            None,
+            msg,
+            &token_id,
+            1,
+            GAS_FOR_FT_TRANSFER_CALL
+        ).then(ext_self::on_claim_call_resolved(
+            account_id,
+            token_id,
+            amount,
+            claim_index,
+            used_expiring_amounts,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ))
+    }
+
+    /// Re-credits the claimed amount if `ft_transfer` failed (e.g. receiver is not registered).
+    /// The re-credited amount keeps the expiry of the amounts it was claimed from. Returns the amount that was actually transferred.
+    #[private]
+    pub fn on_claim_resolved(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        claim_index: u64,
+        used_expiring_amounts: Vec<ExpiringAmount>,
+    ) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                self.internal_resolve_claim(&account_id, &token_id, claim_index, ClaimStatus::Succeeded, 0, vec![]);
+                events::emit_claim_reward_resolved(&account_id, &token_id, amount.into(), true);
+                amount
+            }
+            PromiseResult::Failed => {
+                self.internal_resolve_claim(&account_id, &token_id, claim_index, ClaimStatus::Failed, amount.into(), used_expiring_amounts);
+                events::emit_claim_reward_resolved(&account_id, &token_id, amount.into(), false);
+                U128(0)
+            }
+        }
+    }
+
+    /// Re-credits the part of the claimed amount that `ft_transfer_call` did not use, with the expiry it was claimed from.
+    /// Returns the amount that was actually used by the receiver.
+    #[private]
+    pub fn on_claim_call_resolved(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        claim_index: u64,
+        used_expiring_amounts: Vec<ExpiringAmount>,
+    ) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
         let amount: u128 = amount.into();
-        assert!(amount <= current_amount, "ERR_AMOUNT_TOO_HIGH");
+        let used_amount: u128 = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value)
+                .map(|used_amount| std::cmp::min(used_amount.into(), amount))
+                .unwrap_or(amount),
+            PromiseResult::Failed => 0,
+        };
+        let refund_amount = amount - used_amount;
+        let status = if used_amount > 0 { ClaimStatus::Succeeded } else { ClaimStatus::Failed };
+        self.internal_resolve_claim(&account_id, &token_id, claim_index, status, refund_amount, used_expiring_amounts);
+        if used_amount > 0 {
+            events::emit_claim_reward_resolved(&account_id, &token_id, used_amount, true);
+        }
+        if refund_amount > 0 {
+            events::emit_claim_reward_resolved(&account_id, &token_id, refund_amount, false);
+        }
+        used_amount.into()
+    }
 
-        log!("Claiming reward : {} PARAS", (amount as f64 / 1e24));
-        current_rewards.internal_set_reward_amount(current_amount.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW"));
+    /// Pushes a reward from the deposit, or from the budget of `campaign_id`.
+    /// Campaign rewards can be pushed by the campaign pushers, other rewards by the owner and distributors.
+    #[payable]
+    #[allow(clippy::too_many_arguments)]
+    pub fn push_reward(
+        &mut self,
+        account_id: ValidAccountId,
+        amount: U128,
+        memo: String,
+        token_id: Option<ValidAccountId>,
+        expires_at: Option<U64>,
+        idempotency_key: Option<String>,
+        campaign_id: Option<u64>,
+    ) {
+        if campaign_id.is_none() {
+            self.assert_distributor();
+        }
+        self.assert_not_paused(Operation::Push);
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        let token_id = self.internal_whitelisted_token_id(token_id);
+        let initial_storage_usage = env::storage_usage();
+        // checked first, so a retry of an applied push always fails with the duplicate error
+        if let Some(idempotency_key) = idempotency_key {
+            assert!(self.applied_keys.insert(&idempotency_key), "ERR_DUPLICATE_IDEMPOTENCY_KEY");
+        }
+        if campaign_id.is_none() {
+            assert!(self.internal_deposited_amount(&token_id) >= amount.into(), "ERR_DEPOSITED_AMOUNT_NOT_ENOUGH");
+        }
+        let source = campaign_id.map_or(RewardSource::Deposit, RewardSource::Campaign);
+        self.internal_push_reward(account_id.as_ref(), &token_id, amount.into(), memo, expires_at.map(|e| e.into()), source, &env::predecessor_account_id());
+        self.internal_charge_storage(Some(account_id.as_ref()), initial_storage_usage);
+    }
 
-        self.records.insert(&env::predecessor_account_id(), &current_rewards);
+    /// Pushes a reward that unlocks linearly over `duration` nanoseconds from `start`.
+    /// Nothing is claimable before `start + cliff`.
+    #[payable]
+    #[allow(clippy::too_many_arguments)]
+    pub fn push_vesting_reward(
+        &mut self,
+        account_id: ValidAccountId,
+        amount: U128,
+        memo: String,
+        token_id: Option<ValidAccountId>,
+        start: U64,
+        cliff: U64,
+        duration: U64,
+    ) {
+        self.assert_distributor();
+        self.assert_not_paused(Operation::Push);
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        let token_id = self.internal_whitelisted_token_id(token_id);
+        assert!(self.internal_deposited_amount(&token_id) >= amount.into(), "ERR_DEPOSITED_AMOUNT_NOT_ENOUGH");
+        let initial_storage_usage = env::storage_usage();
+        self.internal_push_reward(account_id.as_ref(), &token_id, amount.into(), memo, None, RewardSource::Deposit, &env::predecessor_account_id());
 
-        ext_fungible_token::ft_transfer(
-            env::predecessor_account_id().into(),
+        let mut current_rewards = self.records.get(account_id.as_ref()).unwrap();
+        current_rewards.internal_add_vesting(Vesting::new(
+            current_rewards.get_rewards_len() - 1,
+            token_id.clone(),
             amount.into(),
+            start.into(),
+            cliff.into(),
+            duration.into(),
+        ));
+        self.records.insert(account_id.as_ref(), &current_rewards);
+        self.internal_charge_storage(Some(account_id.as_ref()), initial_storage_usage);
+    }
+
+    /// Takes back the unclaimed part of the reward at `reward_index`, e.g. one pushed to the wrong account,
+    /// and returns it to where it was taken from: the deposit, the open campaign budget or the funder pool. Fails if the reward was already claimed. Returns the revoked amount.
+    #[payable]
+    pub fn revoke_reward(&mut self, account_id: ValidAccountId, reward_index: u64, reason: String) -> U128 {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let initial_storage_usage = env::storage_usage();
+        let mut current_rewards = self.internal_get_rewards(account_id.as_ref()).expect("ERR_NO_REWARD");
+        let (token_id, revoked_amount) = current_rewards.internal_revoke_reward(reward_index, reason.clone(), env::block_timestamp());
+        self.records.insert(account_id.as_ref(), &current_rewards);
+        let source = current_rewards.get_reward(reward_index).get_source();
+        self.internal_return_reward_amount(&token_id, &source, revoked_amount);
+        self.internal_update_stats(&token_id, |stats| stats.release(revoked_amount));
+        self.internal_charge_storage(None, initial_storage_usage);
+
+        events::emit_revoke_reward(account_id.as_ref(), &token_id, revoked_amount, reward_index, &reason);
+        revoked_amount.into()
+    }
+
+    /// Stops the vesting of the reward at `reward_index` and returns the unvested part to the deposit.
+    /// The already vested part stays claimable. Returns the revoked amount.
+    #[payable]
+    pub fn revoke_vesting(&mut self, account_id: ValidAccountId, reward_index: u64) -> U128 {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let initial_storage_usage = env::storage_usage();
+        let mut current_rewards = self.internal_get_rewards(account_id.as_ref()).expect("ERR_NO_REWARD");
+        let (token_id, unvested_amount) = current_rewards.internal_revoke_vesting(reward_index, env::block_timestamp());
+        self.records.insert(account_id.as_ref(), &current_rewards);
+        self.internal_deposit(&token_id, unvested_amount);
+        self.internal_update_stats(&token_id, |stats| stats.release(unvested_amount));
+        self.internal_charge_storage(None, initial_storage_usage);
+
+        events::emit_revoke_vesting(account_id.as_ref(), &token_id, unvested_amount, reward_index);
+        unvested_amount.into()
+    }
+
+    pub fn get_vestings(&self, account_id: ValidAccountId) -> Vec<WrappedVesting> {
+        self.internal_view_rewards(account_id.as_ref())
+            .map(|current_rewards| current_rewards.get_vestings(env::block_timestamp()))
+            .unwrap_or_default()
+    }
+
+    /// Pushes rewards of a single token to many accounts in one transaction.
+    /// Either every entry is applied or none is. Returns the new reward amount of each entry's account.
+    #[payable]
+    pub fn push_rewards_batch(
+        &mut self,
+        rewards: Vec<(ValidAccountId, U128, String)>,
+        token_id: Option<ValidAccountId>,
+        expires_at: Option<U64>,
+    ) -> Vec<(AccountId, U128)> {
+        self.assert_distributor();
+        self.assert_not_paused(Operation::Push);
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        assert!(!rewards.is_empty(), "ERR_BATCH_EMPTY");
+        assert!(rewards.len() as u64 <= MAX_REWARDS_PER_BATCH, "ERR_BATCH_TOO_LARGE");
+        assert!(
+            env::prepaid_gas().saturating_sub(env::used_gas()) >= GAS_FOR_PUSH_REWARD * rewards.len() as u64,
+            "ERR_NOT_ENOUGH_GAS"
+        );
+        let token_id = self.internal_whitelisted_token_id(token_id);
+
+        let total_amount = rewards.iter()
+            .try_fold(0u128, |total, (_, amount, _)| total.checked_add(amount.0))
+            .expect("ERR_INTEGER_OVERFLOW");
+        assert!(self.internal_deposited_amount(&token_id) >= total_amount, "ERR_DEPOSITED_AMOUNT_NOT_ENOUGH");
+
+        let initial_storage_usage = env::storage_usage();
+        let pushed_by = env::predecessor_account_id();
+        let results: Vec<(AccountId, U128)> = rewards.into_iter()
+            .map(|(account_id, amount, memo)| {
+                let current_amount = self.internal_push_reward(account_id.as_ref(), &token_id, amount.into(), memo, expires_at.map(|e| e.into()), RewardSource::Deposit, &pushed_by);
+                (account_id.into(), current_amount.into())
+            })
+            .collect();
+        self.internal_charge_storage(None, initial_storage_usage);
+        results
+    }
+
+    /// Sends undistributed deposit back to `receiver_id`, e.g. the treasury.
+    #[payable]
+    pub fn withdraw_deposit(&mut self, amount: U128, receiver_id: ValidAccountId, token_id: Option<ValidAccountId>) -> Promise {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let token_id = self.internal_token_id(token_id);
+        self.internal_withdraw_deposit(&token_id, amount.into());
+
+        events::emit_withdraw_deposit(&token_id, receiver_id.as_ref(), amount.into());
+
+        ext_fungible_token::ft_transfer(
+            receiver_id,
+            amount,
             None,
-            &self.token,
+            &token_id,
             1,
             GAS_FOR_FT_TRANSFER
-        )
+        ).then(ext_self::on_withdraw_deposit_resolved(
+            token_id.clone(),
+            amount,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ))
+    }
+
+    /// Restores the deposit if the withdrawal `ft_transfer` failed.
+    /// Returns the amount that was actually withdrawn.
+    #[private]
+    pub fn on_withdraw_deposit_resolved(&mut self, token_id: AccountId, amount: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => amount,
+            PromiseResult::Failed => {
+                self.internal_deposit(&token_id, amount.into());
+                log!("Withdraw failed, restored deposit {} {}", u128::from(amount), token_id);
+                U128(0)
+            }
+        }
     }
 
+    /// Returns expired, unclaimed rewards of `account_ids` to where they were taken from: the deposit,
+    /// the open campaign budget or the funder pool.
+    /// Returns the swept amount per token.
     #[payable]
-    pub fn push_reward(&mut self, account_id: ValidAccountId, amount: U128, memo: String) {
+    pub fn sweep_expired(&mut self, account_ids: Vec<ValidAccountId>) -> HashMap<AccountId, U128> {
         assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
         assert_one_yocto();
-        assert!(self.deposited_amount >= amount.into(), "ERR_DEPOSITED_AMOUNT_NOT_ENOUGH");
-        let mut current_rewards = self.records.get(account_id.as_ref()).unwrap_or(Rewards::new(account_id.clone().into()));
+        let initial_storage_usage = env::storage_usage();
+        let mut swept_amounts: HashMap<AccountId, u128> = HashMap::new();
+        for account_id in account_ids {
+            if let Some(mut current_rewards) = self.internal_get_rewards(account_id.as_ref()) {
+                let expired_amounts = current_rewards.internal_take_expired_amounts(env::block_timestamp());
+                if expired_amounts.is_empty() {
+                    continue;
+                }
+                self.records.insert(account_id.as_ref(), &current_rewards);
+                for ((token_id, source), expired_amount) in expired_amounts {
+                    self.internal_return_reward_amount(&token_id, &source, expired_amount);
+                    self.internal_update_stats(&token_id, |stats| stats.release(expired_amount));
+                    events::emit_sweep_expired(account_id.as_ref(), &token_id, expired_amount);
+                    *swept_amounts.entry(token_id).or_insert(0) += expired_amount;
+                }
+            }
+        }
+        self.internal_charge_storage(None, initial_storage_usage);
+        swept_amounts.into_iter().map(|(token_id, amount)| (token_id, amount.into())).collect()
+    }
+
+    /// Takes `amount` of `token_id` out of the claimable balance of `account_id` and records a pending claim to `receiver_id`.
+    /// The claim history entry is paid by the account storage balance, claims never draw from the owner storage pool.
+    /// Returns the claim index and the expiring amounts the claim used.
+    fn internal_claim_reward(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AccountId,
+        amount: u128,
+        receiver_id: &AccountId,
+    ) -> (u64, Vec<ExpiringAmount>) {
+        assert!(amount > 0, "ERR_AMOUNT_ZERO");
+        assert!(self.storage_deposits.contains_key(account_id), "ERR_STORAGE_NOT_REGISTERED");
+        let initial_storage_usage = env::storage_usage();
+        let mut current_rewards = self.internal_get_rewards(account_id).expect("ERR_NO_REWARDS");
+        let current_amount = current_rewards.internal_reward_amount(token_id);
+        assert!(amount <= current_rewards.internal_claimable_amount(token_id, env::block_timestamp()), "ERR_AMOUNT_TOO_HIGH");
+
+        events::emit_claim_reward(account_id, token_id, amount);
+        let used_expiring_amounts = current_rewards.internal_use_expiring_amount(token_id, amount, env::block_timestamp());
+        current_rewards.internal_release_vested(env::block_timestamp());
+        current_rewards.internal_set_reward_amount(token_id, current_amount.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW"));
+
+        let claim_index = current_rewards.internal_add_claim(token_id, amount, receiver_id);
+
+        self.records.insert(account_id, &current_rewards);
+        self.internal_update_stats(token_id, |stats| stats.claim(amount));
+        self.internal_charge_account_storage(account_id, initial_storage_usage);
+        (claim_index, used_expiring_amounts)
+    }
+
+    /// Records the outcome of a claim. The `refunded_amount` is added back to the balance of `account_id`,
+    /// expiring again where it was taken from `used_expiring_amounts`.
+    fn internal_resolve_claim(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AccountId,
+        claim_index: u64,
+        status: ClaimStatus,
+        refunded_amount: u128,
+        used_expiring_amounts: Vec<ExpiringAmount>,
+    ) {
+        let mut current_rewards = self.internal_get_rewards(account_id).expect("ERR_NO_REWARDS");
+        current_rewards.internal_resolve_claim(claim_index, status, refunded_amount);
+        if refunded_amount > 0 {
+            current_rewards.internal_restore_expiring_amounts(used_expiring_amounts, refunded_amount);
+            let current_amount = current_rewards.internal_reward_amount(token_id);
+            current_rewards.internal_set_reward_amount(token_id, current_amount.checked_add(refunded_amount).expect("ERR_INTEGER_OVERFLOW"));
+            self.internal_update_stats(token_id, |stats| stats.refund_claim(refunded_amount));
+        }
+        self.records.insert(account_id, &current_rewards);
+    }
+
+    /// Gives back the revoked or expired `amount` of a reward to the `source` it was taken from.
+    fn internal_return_reward_amount(&mut self, token_id: &AccountId, source: &RewardSource, amount: u128) {
+        match source {
+            RewardSource::Deposit => self.internal_deposit(token_id, amount),
+            RewardSource::Campaign(campaign_id) => self.internal_return_campaign_budget(*campaign_id, token_id, amount),
+            RewardSource::Funder(funder_id) => self.internal_funder_deposit(funder_id, token_id, amount),
+        }
+    }
+
+    /// Moves `amount` of `token_id` from `source` into `account_id` rewards.
+    /// Returns the account's new reward amount.
+    #[allow(clippy::too_many_arguments)]
+    fn internal_push_reward(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AccountId,
+        amount: u128,
+        memo: String,
+        expires_at: Option<u64>,
+        source: RewardSource,
+        pushed_by: &AccountId,
+    ) -> u128 {
+        let mut current_rewards = self.internal_get_rewards(account_id).unwrap_or_else(|| {
+            self.num_reward_accounts += 1;
+            Rewards::new(account_id.clone())
+        });
         let new_reward: Reward = Reward::new(
+            self.next_reward_id,
+            token_id.clone(),
             amount.into(),
-            memo,
+            memo.clone(),
+            expires_at,
+            pushed_by.clone(),
+            source.clone(),
         );
-        self.deposited_amount = self.deposited_amount.checked_sub(amount.into()).expect("ERR_INTEGER_OVERFLOW");
+        self.next_reward_id += 1;
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at > env::block_timestamp(), "ERR_INVALID_EXPIRES_AT");
+            current_rewards.internal_add_expiring_amount(token_id, amount, expires_at, source.clone());
+        }
+        match source {
+            RewardSource::Deposit => self.internal_withdraw_deposit(token_id, amount),
+            RewardSource::Campaign(campaign_id) => self.internal_use_campaign_budget(campaign_id, token_id, amount),
+            RewardSource::Funder(funder_id) => self.internal_funder_withdraw(&funder_id, token_id, amount),
+        }
+        self.internal_update_stats(token_id, |stats| stats.push(amount));
 
         // insert new record to current_record and set reward amount
-        let current_amount = current_rewards.internal_reward_amount();
+        let current_amount = current_rewards.internal_reward_amount(token_id);
         current_rewards.internal_add_new_reward(new_reward);
-        current_rewards.internal_set_reward_amount(current_amount.checked_add(amount.into()).expect("ERR_INTEGER_OVERFLOW"));
-        self.records.insert(account_id.as_ref(), &current_rewards);
+        current_rewards.internal_set_reward_amount(token_id, current_amount.checked_add(amount).expect("ERR_INTEGER_OVERFLOW"));
+        self.records.insert(account_id, &current_rewards);
 
-        log!("Current reward for {} : {} PARAS", account_id.to_string(), current_rewards.internal_reward_amount() as f64 / 1e24);
+        events::emit_push_reward(account_id, token_id, amount, &memo, current_rewards.get_rewards_len() - 1);
 
+        current_rewards.internal_reward_amount(token_id)
     }
 
 }
 
+/// Page of the entries in `[start_index, end_index)`, newest first, skipping the `from_index` newest.
+fn internal_page<T, F: Fn(u64) -> T>(start_index: u64, end_index: u64, from_index: u64, limit: u64, get_entry: F) -> Vec<T> {
+    let last_index = std::cmp::max(start_index, end_index.saturating_sub(from_index));
+    let first_index = std::cmp::max(start_index, last_index.saturating_sub(limit));
+    (first_index..last_index).rev().map(get_entry).collect()
+}
+
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
     use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
     use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env};
+    use near_sdk::{testing_env, PromiseOrValue};
+    use near_sdk::serde_json::{self, json, Value};
 
 
     const TEN_PARAS_TOKEN: U128 = U128(10_000_000_000_000_000_000_000_000);
+    const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
 
     fn get_context(predecessor_account_id: ValidAccountId) -> VMContextBuilder {
         let mut builder = VMContextBuilder::new();
@@ -125,10 +684,22 @@ mod tests {
         builder
     }
 
+    fn get_event_logs() -> Vec<Value> {
+        get_logs().iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|event| serde_json::from_str(event).unwrap())
+            .collect()
+    }
+
     fn setup_contract() -> (VMContextBuilder, Contract) {
         let mut context = VMContextBuilder::new();
         testing_env!(context.predecessor_account_id(accounts(0)).build());
-        let contract = Contract::new(accounts(1).into(), accounts(2).into());
+        let mut contract = Contract::new(accounts(1).into(), accounts(2).into());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(ONE_NEAR)
+                .build());
+        contract.storage_deposit(None, None);
         (context, contract)
     }
 
@@ -138,7 +709,7 @@ mod tests {
         testing_env!(context.build());
         let contract = Contract::new(accounts(1).into(), accounts(2).into());
         testing_env!(context.is_view(true).build());
-        assert_eq!(contract.deposited_amount, 0);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
         assert_eq!(contract.owner, accounts(1).to_string());
         assert_eq!(contract.token, accounts(2).to_string());
     }
@@ -159,7 +730,7 @@ mod tests {
                 .attached_deposit(1)
                 .build());
         contract.ft_on_transfer(accounts(3), U128(10), "".to_string());
-        assert_eq!(contract.deposited_amount, 10);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 10);
     }
 
     #[test]
@@ -174,13 +745,77 @@ mod tests {
                 .predecessor_account_id(accounts(1))
                 .attached_deposit(1)
                 .build());
-        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string());
-        assert_eq!(contract.deposited_amount, 0);
-        assert_eq!(contract.get_reward_amount(accounts(3)), TEN_PARAS_TOKEN.into());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), TEN_PARAS_TOKEN.into());
         assert_eq!(contract.records.get(accounts(3).as_ref()).unwrap().get_reward(0).get_amount(), TEN_PARAS_TOKEN.into());
         assert_eq!(contract.records.get(accounts(3).as_ref()).unwrap().get_reward(0).get_memo(), "first reward");
     }
 
+    #[test]
+    fn test_views_unknown_account() {
+        let (_, contract) = setup_contract();
+        assert!(!contract.has_rewards(accounts(3)));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+        assert_eq!(contract.get_rewards(0, 10, accounts(3), None, None).total_count, 0);
+        assert!(contract.get_rewards(0, 10, accounts(3), None, None).rewards.is_empty());
+        assert!(contract.get_vestings(accounts(3)).is_empty());
+    }
+
+    #[test]
+    fn test_get_rewards_page() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        for memo in &["first", "second", "third"] {
+            contract.push_reward(accounts(3), U128(10), memo.to_string(), None, None, None, None);
+        }
+        assert!(contract.has_rewards(accounts(3)));
+        let page = serde_json::to_value(contract.get_rewards(1, 1, accounts(3), None, None)).unwrap();
+        assert_eq!(page["total_count"], 3);
+        assert_eq!(page["rewards"].as_array().unwrap().len(), 1);
+        assert_eq!(page["rewards"][0]["memo"], "second");
+    }
+
+    #[test]
+    fn test_get_rewards_time_range() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        for (timestamp, memo) in &[(100, "first"), (200, "second"), (300, "third")] {
+            testing_env!(context
+                    .predecessor_account_id(accounts(1))
+                    .block_timestamp(*timestamp)
+                    .block_index(*timestamp / 100)
+                    .attached_deposit(1)
+                    .build());
+            contract.push_reward(accounts(3), U128(10), memo.to_string(), None, None, None, None);
+        }
+        let page = serde_json::to_value(contract.get_rewards(0, 10, accounts(3), Some(U64(150)), Some(U64(300)))).unwrap();
+        assert_eq!(page["total_count"], 1);
+        assert_eq!(page["rewards"][0]["memo"], "second");
+        assert_eq!(page["rewards"][0]["id"], "1");
+        assert_eq!(page["rewards"][0]["created_at"], "200");
+        assert_eq!(page["rewards"][0]["block_height"], "2");
+        assert_eq!(page["rewards"][0]["pushed_by"], "bob");
+
+        let page = serde_json::to_value(contract.get_rewards(1, 10, accounts(3), Some(U64(100)), None)).unwrap();
+        assert_eq!(page["total_count"], 3);
+        assert_eq!(page["rewards"].as_array().unwrap().len(), 2);
+        assert_eq!(page["rewards"][0]["memo"], "second");
+        assert_eq!(page["rewards"][1]["memo"], "first");
+    }
+
     #[test]
     fn test_claim_reward() {
         let (mut context, mut contract) = setup_contract();
@@ -193,13 +828,1632 @@ mod tests {
                 .predecessor_account_id(accounts(1))
                 .attached_deposit(1)
                 .build());
-        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(TEN_PARAS_TOKEN, None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+    }
+
+    #[test]
+    fn test_claim_reward_failed_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(TEN_PARAS_TOKEN, None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let claimed = contract.on_claim_resolved(accounts(3).into(), accounts(2).into(), TEN_PARAS_TOKEN, 0, vec![]);
+        assert_eq!(claimed, U128(0));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), TEN_PARAS_TOKEN);
+    }
+
+    #[test]
+    fn test_stats() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        contract.push_reward(accounts(4), U128(20), "second reward".to_string(), None, None, None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(4), None);
+
+        let stats = serde_json::to_value(contract.get_stats(None)).unwrap();
+        assert_eq!(stats, json!({
+            "token_id": "charlie",
+            "total_pushed": "30",
+            "total_claimed": "4",
+            "total_outstanding": "26",
+            "total_campaign_budget": "0",
+            "total_funder_balance": "0",
+            "deposited_amount": "70",
+            "num_reward_accounts": 2,
+        }));
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&U128(96)).unwrap())]
+        );
+        assert!(contract.on_audit(accounts(2).into()));
+    }
+
+    #[test]
+    fn test_get_claims() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(ONE_NEAR)
+                .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .block_timestamp(100)
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(4), None);
+        contract.claim_reward_to(accounts(4), U128(6), None);
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.on_claim_resolved(accounts(3).into(), accounts(2).into(), U128(6), 1, vec![]);
+
+        let page = serde_json::to_value(contract.get_claims(accounts(3), 0, 10)).unwrap();
+        assert_eq!(page["total_count"], 2);
+        assert_eq!(page["claims"][0], json!({
+            "token_id": "charlie",
+            "amount": "6",
+            "receiver_id": "eugene",
+            "created_at": "100",
+            "status": "Failed",
+            "refunded_amount": "6",
+        }));
+        assert_eq!(page["claims"][1]["receiver_id"], "danny");
+        assert_eq!(page["claims"][1]["status"], "Pending");
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(6));
+        assert_eq!(contract.get_claims(accounts(4), 0, 10).total_count, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_NOT_REGISTERED")]
+    fn test_claim_without_storage_balance() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(10), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_ZERO")]
+    fn test_claim_zero_amount() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(0), None);
+    }
+
+    #[test]
+    fn test_revoke_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        contract.push_reward(accounts(3), U128(20), "wrong reward".to_string(), None, None, None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(15), None);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        assert_eq!(contract.revoke_reward(accounts(3), 1, "wrong account".to_string()), U128(15));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 85);
+        let page = serde_json::to_value(contract.get_rewards(0, 1, accounts(3), None, None)).unwrap();
+        assert_eq!(page["rewards"][0]["revoked_reason"], "wrong account");
+        assert_eq!(page["rewards"][0]["revoked_amount"], "15");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REWARD_ALREADY_CLAIMED")]
+    fn test_revoke_claimed_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
         testing_env!(context
                 .predecessor_account_id(accounts(3))
                 .attached_deposit(1)
                 .build());
-        contract.claim_reward(TEN_PARAS_TOKEN);
-        assert_eq!(contract.get_reward_amount(accounts(3)), U128(0));
+        contract.claim_reward(U128(10), None);
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.revoke_reward(accounts(3), 0, "wrong account".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DUPLICATE_IDEMPOTENCY_KEY")]
+    fn test_push_reward_idempotency_key() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        assert!(!contract.is_key_applied("campaign-1:danny".to_string()));
+        contract.push_reward(accounts(3), U128(10), "".to_string(), None, None, Some("campaign-1:danny".to_string()), None);
+        assert!(contract.is_key_applied("campaign-1:danny".to_string()));
+        contract.push_reward(accounts(3), U128(10), "".to_string(), None, None, Some("campaign-1:danny".to_string()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DUPLICATE_IDEMPOTENCY_KEY")]
+    fn test_push_reward_idempotency_key_after_deposit_used() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(10), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "".to_string(), None, None, Some("campaign-1:danny".to_string()), None);
+        contract.push_reward(accounts(3), U128(10), "".to_string(), None, None, Some("campaign-1:danny".to_string()), None);
+    }
+
+    fn setup_campaign(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        let campaign_id = contract.create_campaign("weekly creator rewards".to_string(), None, U64(100), U64(1000), vec![accounts(5)]);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(30), json!({"campaign": campaign_id}).to_string());
+        campaign_id
+    }
+
+    #[test]
+    fn test_campaign() {
+        let (mut context, mut contract) = setup_contract();
+        let campaign_id = setup_campaign(&mut context, &mut contract);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+        testing_env!(context
+                .predecessor_account_id(accounts(5))
+                .block_timestamp(100)
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "creator reward".to_string(), None, None, None, Some(campaign_id));
+
+        let campaign = serde_json::to_value(contract.get_campaign(campaign_id)).unwrap();
+        assert_eq!(campaign["budget"], "30");
+        assert_eq!(campaign["spent_amount"], "10");
+        assert_eq!(campaign["remaining_budget"], "20");
+        assert_eq!(campaign["num_rewards"], 1);
+        assert_eq!(contract.list_campaigns(0, 10).len(), 1);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(10));
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .block_timestamp(1000)
+                .attached_deposit(1)
+                .build());
+        assert_eq!(contract.close_campaign(campaign_id), U128(20));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 20);
+    }
+
+    #[test]
+    fn test_campaign_revoke_and_sweep() {
+        let (mut context, mut contract) = setup_contract();
+        let campaign_id = setup_campaign(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(5))
+                .block_timestamp(100)
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "creator reward".to_string(), None, None, None, Some(campaign_id));
+        contract.push_reward(accounts(4), U128(5), "creator reward".to_string(), None, Some(U64(500)), None, Some(campaign_id));
+        let rewards = serde_json::to_value(contract.get_rewards(0, 10, accounts(3), None, None)).unwrap();
+        assert_eq!(rewards["rewards"][0]["campaign_id"], campaign_id);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .block_timestamp(200)
+                .attached_deposit(1)
+                .build());
+        contract.revoke_reward(accounts(3), 0, "pushed to the wrong account".to_string());
+        let campaign = serde_json::to_value(contract.get_campaign(campaign_id)).unwrap();
+        assert_eq!(campaign["spent_amount"], "5");
+        assert_eq!(campaign["remaining_budget"], "25");
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .block_timestamp(500)
+                .attached_deposit(1)
+                .build());
+        contract.sweep_expired(vec![accounts(4)]);
+        let campaign = serde_json::to_value(contract.get_campaign(campaign_id)).unwrap();
+        assert_eq!(campaign["spent_amount"], "0");
+        assert_eq!(campaign["remaining_budget"], "30");
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+        assert_eq!(serde_json::to_value(contract.get_stats(None)).unwrap()["total_campaign_budget"], "30");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CAMPAIGN_BUDGET_NOT_ENOUGH")]
+    fn test_campaign_overspend() {
+        let (mut context, mut contract) = setup_contract();
+        let campaign_id = setup_campaign(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(5))
+                .block_timestamp(100)
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(31), "creator reward".to_string(), None, None, None, Some(campaign_id));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_CAMPAIGN_PUSHER")]
+    fn test_campaign_not_pusher() {
+        let (mut context, mut contract) = setup_contract();
+        let campaign_id = setup_campaign(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .block_timestamp(100)
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "creator reward".to_string(), None, None, None, Some(campaign_id));
+    }
+
+    fn register_storage(context: &mut VMContextBuilder, contract: &mut Contract, account_id: ValidAccountId) {
+        testing_env!(context
+                .predecessor_account_id(account_id)
+                .attached_deposit(ONE_NEAR)
+                .build());
+        contract.storage_deposit(None, None);
+    }
+
+    fn grant_funder(context: &mut VMContextBuilder, contract: &mut Contract) {
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.grant_role(accounts(4), Role::Funder);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(ONE_NEAR)
+                .build());
+        contract.storage_deposit(None, None);
+    }
+
+    #[test]
+    fn test_ft_on_transfer_reward() {
+        let (mut context, mut contract) = setup_contract();
+        grant_funder(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        let unused = contract.ft_on_transfer(
+            accounts(4),
+            U128(10),
+            json!({"reward": {"account_id": accounts(3), "memo": "partner reward"}}).to_string()
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(10));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+        let rewards = serde_json::to_value(contract.get_rewards(0, 10, accounts(3), None, None)).unwrap();
+        assert_eq!(rewards["rewards"][0]["pushed_by"], accounts(4).to_string());
+        assert_eq!(rewards["rewards"][0]["funded_by"], accounts(4).to_string());
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(0));
+    }
+
+    #[test]
+    fn test_ft_on_transfer_batch_refund() {
+        let (mut context, mut contract) = setup_contract();
+        grant_funder(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        let unused = contract.ft_on_transfer(
+            accounts(4),
+            U128(30),
+            json!({"batch": [
+                {"account_id": accounts(3), "amount": "10", "memo": "partner reward"},
+                {"account_id": accounts(5), "amount": "15", "memo": "partner reward"},
+            ]}).to_string()
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(5))));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(10));
+        assert_eq!(contract.get_reward_amount(accounts(5), None), U128(15));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_EXCEEDS_TRANSFER")]
+    fn test_ft_on_transfer_batch_exceeds_amount() {
+        let (mut context, mut contract) = setup_contract();
+        grant_funder(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(
+            accounts(4),
+            U128(20),
+            json!({"batch": [
+                {"account_id": accounts(3), "amount": "10", "memo": ""},
+                {"account_id": accounts(5), "amount": "15", "memo": ""},
+            ]}).to_string()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_FUNDER")]
+    fn test_ft_on_transfer_reward_not_funder() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(
+            accounts(4),
+            U128(1),
+            json!({"reward": {"account_id": accounts(3), "memo": "partner reward"}}).to_string()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_DEPOSIT_NOT_ENOUGH")]
+    fn test_ft_on_transfer_reward_without_storage() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.grant_role(accounts(4), Role::Funder);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(
+            accounts(4),
+            U128(1),
+            json!({"reward": {"account_id": accounts(3), "memo": "partner reward"}}).to_string()
+        );
+    }
+
+    fn setup_funder(context: &mut VMContextBuilder, contract: &mut Contract) {
+        grant_funder(context, contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(4), U128(100), json!({"funder_deposit": {}}).to_string());
+    }
+
+    #[test]
+    fn test_funder() {
+        let (mut context, mut contract) = setup_contract();
+        setup_funder(&mut context, &mut contract);
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(100));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        assert_eq!(contract.push_funded_reward(accounts(3), U128(30), "partner reward".to_string(), None, None), U128(30));
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(70));
+        let rewards = serde_json::to_value(contract.get_rewards(0, 10, accounts(3), None, None)).unwrap();
+        assert_eq!(rewards["rewards"][0]["funded_by"], accounts(4).to_string());
+        let stats = serde_json::to_value(contract.get_stats(None)).unwrap();
+        assert_eq!(stats["total_funder_balance"], "70");
+        assert_eq!(stats["total_outstanding"], "30");
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.revoke_reward(accounts(3), 0, "pushed to the wrong account".to_string());
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(100));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+    }
+
+    #[test]
+    fn test_sweep_funded_reward() {
+        let (mut context, mut contract) = setup_contract();
+        setup_funder(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_funded_reward(accounts(3), U128(30), "partner reward".to_string(), None, Some(U64(200)));
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(70));
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(200)
+                .build());
+        let swept = contract.sweep_expired(vec![accounts(3)]);
+        assert_eq!(swept.get(&accounts(2).to_string()), Some(&U128(30)));
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(100));
+        assert_eq!(
+            get_event_logs(),
+            vec![json!({
+                "standard": "paras_claim_rewards",
+                "version": "1.0.0",
+                "event": "sweep_expired",
+                "data": [{"account_id": "danny", "token_id": "charlie", "amount": "30"}],
+            })]
+        );
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_FUNDER")]
+    fn test_funder_deposit_not_funder() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(4), U128(100), json!({"funder_deposit": {}}).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FUNDER_BALANCE_NOT_ENOUGH")]
+    fn test_push_funded_reward_balance_not_enough() {
+        let (mut context, mut contract) = setup_contract();
+        setup_funder(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.push_funded_reward(accounts(3), U128(101), "partner reward".to_string(), None, None);
+    }
+
+    #[test]
+    fn test_withdraw_funder_balance_failed() {
+        let (mut context, mut contract) = setup_contract();
+        setup_funder(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.withdraw_funder_balance(U128(40), None);
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(60));
+        assert_eq!(
+            get_event_logs(),
+            vec![json!({
+                "standard": "paras_claim_rewards",
+                "version": "1.0.0",
+                "event": "funder_withdraw",
+                "data": [{"funder_id": "eugene", "token_id": "charlie", "amount": "40"}],
+            })]
+        );
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let withdrawn = contract.on_funder_withdraw_resolved(accounts(4).into(), accounts(2).into(), U128(40));
+        assert_eq!(withdrawn, U128(0));
+        assert_eq!(contract.get_funder_balance(accounts(4), None), U128(100));
+    }
+
+    #[test]
+    fn test_claim_reward_call_refund() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), U128(10), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward_call(accounts(4), U128(10), "stake".to_string(), None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&U128(6)).unwrap())]
+        );
+        let used = contract.on_claim_call_resolved(accounts(3).into(), accounts(2).into(), U128(10), 0, vec![]);
+        assert_eq!(used, U128(6));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(4));
+    }
+
+    #[test]
+    fn test_claim_reward_to_failed_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), U128(10), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "first reward".to_string(), None, None, None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward_to(accounts(4), U128(10), None);
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.on_claim_resolved(accounts(3).into(), accounts(2).into(), U128(10), 0, vec![]);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(10));
+    }
+
+    #[test]
+    fn test_push_rewards_batch() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        let results = contract.push_rewards_batch(vec![
+            (accounts(3), U128(4_000_000_000_000_000_000_000_000), "first reward".to_string()),
+            (accounts(4), U128(5_000_000_000_000_000_000_000_000), "first reward".to_string()),
+            (accounts(3), U128(1_000_000_000_000_000_000_000_000), "second reward".to_string()),
+        ], None, None);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2], (accounts(3).to_string(), U128(5_000_000_000_000_000_000_000_000)));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 0);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(5_000_000_000_000_000_000_000_000));
+        assert_eq!(contract.get_reward_amount(accounts(4), None), U128(5_000_000_000_000_000_000_000_000));
+        assert_eq!(contract.records.get(accounts(3).as_ref()).unwrap().get_rewards_len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSITED_AMOUNT_NOT_ENOUGH")]
+    fn test_not_enough_push_rewards_batch() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_rewards_batch(vec![
+            (accounts(3), TEN_PARAS_TOKEN, "".to_string()),
+            (accounts(4), U128(1), "".to_string()),
+        ], None, None);
+    }
+
+    #[test]
+    fn test_storage_deposit_and_withdraw() {
+        let (mut context, mut contract) = setup_contract();
+        assert_eq!(contract.storage_balance_of(accounts(1)).unwrap().total, U128(ONE_NEAR));
+        assert!(contract.storage_balance_of(accounts(3)).is_none());
+
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        let owner_balance: u128 = contract.storage_balance_of(accounts(1)).unwrap().available.into();
+        assert!(owner_balance < ONE_NEAR);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        let storage_balance = contract.storage_withdraw(None);
+        assert_eq!(storage_balance.available, U128(0));
+    }
+
+    #[test]
+    fn test_push_reward_paid_by_attached_deposit() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(ONE_NEAR)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        assert_eq!(contract.storage_balance_of(accounts(1)).unwrap().available, U128(ONE_NEAR));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_DEPOSIT_NOT_ENOUGH")]
+    fn test_push_reward_without_storage() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = Contract::new(accounts(1).into(), accounts(2).into());
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(3), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+    }
+
+    #[test]
+    fn test_multi_token_rewards() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.whitelist_token(accounts(5));
+        assert_eq!(contract.get_whitelisted_tokens(), vec![accounts(2).to_string(), accounts(5).to_string()]);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(5))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(5), "".to_string());
+        assert_eq!(contract.internal_deposited_amount(&accounts(5).to_string()), 5);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(5), "partner reward".to_string(), Some(accounts(5)), None, None, None);
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        assert_eq!(contract.get_reward_amount(accounts(3), Some(accounts(5))), U128(5));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), TEN_PARAS_TOKEN);
+
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(5), Some(accounts(5)));
+        assert_eq!(contract.get_reward_amount(accounts(3), Some(accounts(5))), U128(0));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), TEN_PARAS_TOKEN);
+    }
+
+    #[test]
+    fn test_claim_removed_token() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.whitelist_token(accounts(5));
+        testing_env!(context
+                .predecessor_account_id(accounts(5))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(10), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), U128(5), "partner reward".to_string(), Some(accounts(5)), None, None, None);
+        contract.remove_whitelisted_token(accounts(5));
+        assert_eq!(contract.get_reward_amount(accounts(3), Some(accounts(5))), U128(5));
+
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(5), Some(accounts(5)));
+        assert_eq!(contract.get_reward_amount(accounts(3), Some(accounts(5))), U128(0));
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.withdraw_deposit(U128(5), accounts(1), Some(accounts(5)));
+        assert_eq!(contract.internal_deposited_amount(&accounts(5).to_string()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOKEN_NOT_WHITELISTED")]
+    fn test_deposit_not_whitelisted_token() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(5))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+    }
+
+    #[test]
+    fn test_withdraw_deposit() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.withdraw_deposit(U128(4), accounts(1), None);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), TEN_PARAS_TOKEN.0 - 4);
+        assert_eq!(
+            get_event_logs(),
+            vec![json!({
+                "standard": "paras_claim_rewards",
+                "version": "1.0.0",
+                "event": "withdraw_deposit",
+                "data": [{"token_id": "charlie", "receiver_id": "bob", "amount": "4"}],
+            })]
+        );
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let withdrawn = contract.on_withdraw_deposit_resolved(accounts(2).into(), U128(4));
+        assert_eq!(withdrawn, U128(0));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), TEN_PARAS_TOKEN.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSITED_AMOUNT_NOT_ENOUGH")]
+    fn test_withdraw_deposit_too_much() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.withdraw_deposit(U128(1), accounts(1), None);
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.propose_owner(accounts(4));
+        assert_eq!(contract.get_pending_owner(), Some(accounts(4).to_string()));
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), accounts(4).to_string());
+        assert_eq!(contract.get_pending_owner(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PENDING_OWNER")]
+    fn test_accept_ownership_not_pending_owner() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.propose_owner(accounts(4));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.accept_ownership();
+    }
+
+    #[test]
+    fn test_distributor_push_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.grant_role(accounts(4), Role::Distributor);
+        assert_eq!(contract.get_roles(accounts(4)), vec![Role::Distributor]);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), TEN_PARAS_TOKEN);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn test_distributor_withdraw_deposit() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.grant_role(accounts(4), Role::Distributor);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.withdraw_deposit(U128(1), accounts(4), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_DISTRIBUTOR")]
+    fn test_revoked_distributor_push_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.grant_role(accounts(4), Role::Distributor);
+        contract.revoke_role(accounts(4), Role::Distributor);
+        assert!(contract.get_roles(accounts(4)).is_empty());
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+    }
+
+    #[test]
+    fn test_expired_reward_sweep() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(30), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "seasonal".to_string(), None, Some(U64(200)), None, None);
+        contract.push_reward(accounts(3), U128(10), "permanent".to_string(), None, None, None, None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(20));
+
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .block_timestamp(150)
+                .build());
+        contract.claim_reward(U128(5), None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(15));
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(200)
+                .build());
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(10));
+        let swept = contract.sweep_expired(vec![accounts(3), accounts(4)]);
+        assert_eq!(swept.get(&accounts(2).to_string()), Some(&U128(5)));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 15);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(10));
+    }
+
+    #[test]
+    fn test_failed_claim_keeps_expiry() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(30), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "seasonal".to_string(), None, Some(U64(200)), None, None);
+        contract.push_reward(accounts(3), U128(10), "permanent".to_string(), None, None, None, None);
+
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .block_timestamp(150)
+                .build());
+        contract.claim_reward_to(accounts(4), U128(15), None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(5));
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let used_expiring_amounts = serde_json::from_value(json!([{"token_id": "charlie", "amount": 10, "expires_at": 200, "source": "Deposit"}])).unwrap();
+        contract.on_claim_resolved(accounts(3).into(), accounts(2).into(), U128(15), 0, used_expiring_amounts);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(20));
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(200)
+                .build());
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(10));
+        let swept = contract.sweep_expired(vec![accounts(3)]);
+        assert_eq!(swept.get(&accounts(2).to_string()), Some(&U128(10)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_TOO_HIGH")]
+    fn test_claim_expired_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(10), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_reward(accounts(3), U128(10), "seasonal".to_string(), None, Some(U64(200)), None, None);
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .block_timestamp(200)
+                .build());
+        contract.claim_reward(U128(10), None);
+    }
+
+    #[test]
+    fn test_vesting_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(1_000), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_vesting_reward(accounts(3), U128(1_000), "vesting".to_string(), None, U64(100), U64(10), U64(100));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+
+        testing_env!(context.block_timestamp(109).build());
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+        testing_env!(context.block_timestamp(150).build());
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(500));
+        assert_eq!(contract.get_vestings(accounts(3)).len(), 1);
+
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(U128(500), None);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(0));
+
+        testing_env!(context.block_timestamp(200).build());
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(500));
+        contract.claim_reward(U128(500), None);
+        assert!(contract.get_vestings(accounts(3)).is_empty());
+    }
+
+    #[test]
+    fn test_revoke_vesting() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(1_000), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_vesting_reward(accounts(3), U128(1_000), "vesting".to_string(), None, U64(100), U64(0), U64(100));
+
+        testing_env!(context.block_timestamp(125).build());
+        let revoked = contract.revoke_vesting(accounts(3), 0);
+        assert_eq!(revoked, U128(750));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 750);
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(250));
+        assert_eq!(
+            get_event_logs(),
+            vec![json!({
+                "standard": "paras_claim_rewards",
+                "version": "1.0.0",
+                "event": "revoke_vesting",
+                "data": [{"account_id": "danny", "token_id": "charlie", "amount": "750", "reward_index": 0}],
+            })]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_TOO_HIGH")]
+    fn test_claim_unvested_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(1_000), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_vesting_reward(accounts(3), U128(1_000), "vesting".to_string(), None, U64(100), U64(0), U64(100));
+        register_storage(&mut context, &mut contract, accounts(3));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .block_timestamp(150)
+                .build());
+        contract.claim_reward(U128(501), None);
+    }
+
+    #[test]
+    fn test_push_reward_event() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+        assert_eq!(
+            get_event_logs(),
+            vec![json!({
+                "standard": "paras_claim_rewards",
+                "version": "1.0.0",
+                "event": "deposit",
+                "data": [{"token_id": "charlie", "sender_id": "bob", "amount": "10000000000000000000000000"}],
+            })]
+        );
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        assert_eq!(
+            get_event_logs(),
+            vec![json!({
+                "standard": "paras_claim_rewards",
+                "version": "1.0.0",
+                "event": "push_reward",
+                "data": [{
+                    "account_id": "danny",
+                    "token_id": "charlie",
+                    "amount": "10000000000000000000000000",
+                    "memo": "first reward",
+                    "reward_index": 0,
+                }],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_pause() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        assert!(!contract.is_paused(None));
+        contract.pause(Some(Operation::Claim));
+        assert!(contract.is_paused(Some(Operation::Claim)));
+        assert!(!contract.is_paused(Some(Operation::Push)));
+        assert!(!contract.is_paused(None));
+
+        contract.pause(None);
+        assert!(contract.is_paused(None));
+        contract.unpause(Some(Operation::Deposit));
+        assert!(!contract.is_paused(Some(Operation::Deposit)));
+        assert!(!contract.is_paused(None));
+        contract.unpause(None);
+        assert!(!contract.is_paused(Some(Operation::Claim)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn test_paused_deposit() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.pause(None);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn test_paused_push_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.pause(Some(Operation::Push));
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn test_paused_claim_reward() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), TEN_PARAS_TOKEN, "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.push_reward(accounts(3), TEN_PARAS_TOKEN, "first reward".to_string(), None, None, None, None);
+        contract.pause(Some(Operation::Claim));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_reward(TEN_PARAS_TOKEN, None);
+    }
+
+    #[test]
+    fn test_stage_code() {
+        let (mut context, mut contract) = setup_contract();
+        let mut vm_context = context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(0)
+                .build();
+        vm_context.input = b"new code".to_vec();
+        testing_env!(vm_context);
+        let code_hash = contract.stage_code();
+        assert_eq!(code_hash.0, env::sha256(b"new code"));
+        assert_eq!(contract.get_staged_code_hash().unwrap().0, code_hash.0);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.deploy_staged(code_hash);
+        assert!(contract.get_staged_code_hash().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CODE_HASH_MISMATCH")]
+    fn test_deploy_staged_wrong_hash() {
+        let (mut context, mut contract) = setup_contract();
+        let mut vm_context = context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(0)
+                .build();
+        vm_context.input = b"new code".to_vec();
+        testing_env!(vm_context);
+        contract.stage_code();
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.deploy_staged(env::sha256(b"other code").into());
+    }
+
+    /// Two leaf airdrop of 10 for accounts(3) and 20 for accounts(4), ending at 1000.
+    fn setup_airdrop(context: &mut VMContextBuilder, contract: &mut Contract) -> (Vec<u8>, Vec<u8>) {
+        let leaf_0 = airdrop::airdrop_leaf(0, &accounts(3).into(), 10);
+        let leaf_1 = airdrop::airdrop_leaf(1, &accounts(4).into(), 20);
+        let merkle_root = if leaf_0 <= leaf_1 {
+            env::sha256(&[leaf_0.clone(), leaf_1.clone()].concat())
+        } else {
+            env::sha256(&[leaf_1.clone(), leaf_0.clone()].concat())
+        };
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        let airdrop_id = contract.create_airdrop(merkle_root.into(), U128(30), U64(1000), None);
+        assert_eq!(airdrop_id, 0);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 70);
+        (leaf_0, leaf_1)
+    }
+
+    #[test]
+    fn test_claim_with_proof() {
+        let (mut context, mut contract) = setup_contract();
+        let (leaf_0, _) = setup_airdrop(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.claim_with_proof(0, 1, U128(20), vec![leaf_0.into()]);
+        assert!(contract.is_airdrop_claimed(0, 1));
+        assert!(!contract.is_airdrop_claimed(0, 0));
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .block_timestamp(1000)
+                .build());
+        assert_eq!(contract.close_airdrop(0), U128(10));
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 80);
+    }
+
+    #[test]
+    fn test_claim_with_proof_failed_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        let (leaf_0, _) = setup_airdrop(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+        contract.claim_with_proof(0, 1, U128(20), vec![leaf_0.into()]);
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.on_airdrop_claim_resolved(accounts(4).into(), 0, 1, U128(20)), U128(0));
+        assert!(!contract.is_airdrop_claimed(0, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_CLAIMED")]
+    fn test_claim_with_proof_twice() {
+        let (mut context, mut contract) = setup_contract();
+        let (_, leaf_1) = setup_airdrop(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_with_proof(0, 0, U128(10), vec![leaf_1.clone().into()]);
+        contract.claim_with_proof(0, 0, U128(10), vec![leaf_1.into()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_PROOF")]
+    fn test_claim_with_invalid_proof() {
+        let (mut context, mut contract) = setup_contract();
+        let (_, leaf_1) = setup_airdrop(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_with_proof(0, 0, U128(20), vec![leaf_1.into()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AIRDROP_ENDED")]
+    fn test_claim_with_proof_ended() {
+        let (mut context, mut contract) = setup_contract();
+        let (_, leaf_1) = setup_airdrop(&mut context, &mut contract);
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .block_timestamp(1000)
+                .build());
+        contract.claim_with_proof(0, 0, U128(10), vec![leaf_1.into()]);
+    }
+
+    fn setup_voucher(context: &mut VMContextBuilder, contract: &mut Contract) -> ed25519_dalek::ExpandedSecretKey {
+        let secret_key = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public_key = ed25519_dalek::PublicKey::from(&secret_key);
+        testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .build());
+        contract.ft_on_transfer(accounts(1), U128(100), "".to_string());
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        let mut key = vec![0];
+        key.extend(public_key.as_bytes().iter());
+        contract.set_voucher_signer(Some(near_sdk::json_types::Base58PublicKey(key)));
+        (&secret_key).into()
+    }
+
+    fn sign_voucher(secret_key: &ed25519_dalek::ExpandedSecretKey, voucher: &voucher::Voucher) -> near_sdk::json_types::Base64VecU8 {
+        let public_key = ed25519_dalek::PublicKey::from(secret_key);
+        let message = voucher.to_message(&env::current_account_id(), &accounts(2).into());
+        secret_key.sign(&message, &public_key).to_bytes().to_vec().into()
+    }
+
+    fn new_voucher(nonce: u64) -> voucher::Voucher {
+        voucher::Voucher {
+            account_id: accounts(3),
+            token_id: None,
+            amount: U128(10),
+            nonce: U64(nonce),
+        }
+    }
+
+    #[test]
+    fn test_claim_voucher() {
+        let (mut context, mut contract) = setup_contract();
+        let secret_key = setup_voucher(&mut context, &mut contract);
+        let signature = sign_voucher(&secret_key, &new_voucher(1));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_voucher(new_voucher(1), signature);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 90);
+        assert!(contract.is_voucher_used(accounts(3), U64(1)));
+        assert!(!contract.is_voucher_used(accounts(3), U64(2)));
+    }
+
+    #[test]
+    fn test_claim_vouchers_out_of_order() {
+        let (mut context, mut contract) = setup_contract();
+        let secret_key = setup_voucher(&mut context, &mut contract);
+        let signature_1 = sign_voucher(&secret_key, &new_voucher(1));
+        let signature_2 = sign_voucher(&secret_key, &new_voucher(2));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_voucher(new_voucher(2), signature_2);
+        contract.claim_voucher(new_voucher(1), signature_1);
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 80);
+        assert!(contract.is_voucher_used(accounts(3), U64(1)));
+        assert!(contract.is_voucher_used(accounts(3), U64(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VOUCHER_ALREADY_USED")]
+    fn test_claim_voucher_twice() {
+        let (mut context, mut contract) = setup_contract();
+        let secret_key = setup_voucher(&mut context, &mut contract);
+        let signature = sign_voucher(&secret_key, &new_voucher(1));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_voucher(new_voucher(1), signature.clone());
+        contract.claim_voucher(new_voucher(1), signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_SIGNATURE")]
+    fn test_claim_voucher_invalid_signature() {
+        let (mut context, mut contract) = setup_contract();
+        let secret_key = setup_voucher(&mut context, &mut contract);
+        let signature = sign_voucher(&secret_key, &new_voucher(1));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        let mut voucher = new_voucher(1);
+        voucher.amount = U128(50);
+        contract.claim_voucher(voucher, signature);
+    }
+
+    #[test]
+    fn test_claim_voucher_failed_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        let secret_key = setup_voucher(&mut context, &mut contract);
+        let signature = sign_voucher(&secret_key, &new_voucher(1));
+        testing_env!(context
+                .predecessor_account_id(accounts(3))
+                .attached_deposit(1)
+                .build());
+        contract.claim_voucher(new_voucher(1), signature);
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(
+            contract.on_voucher_claim_resolved(accounts(3).into(), accounts(2).into(), U128(10), U64(1)),
+            U128(0)
+        );
+        assert_eq!(contract.internal_deposited_amount(&accounts(2).to_string()), 100);
+        assert!(!contract.is_voucher_used(accounts(3), U64(1)));
     }
 
     #[test]
@@ -210,6 +2464,6 @@ mod tests {
                 .predecessor_account_id(accounts(1))
                 .attached_deposit(1)
                 .build());
-        contract.push_reward(accounts(3).into(), TEN_PARAS_TOKEN, "".to_string());
+        contract.push_reward(accounts(3).into(), TEN_PARAS_TOKEN, "".to_string(), None, None, None, None);
     }
 }
@@ -0,0 +1,280 @@
+use crate::*;
+use near_sdk::collections::Vector;
+use near_sdk::serde::Serialize;
+use std::collections::HashMap;
+
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Layouts of the contract state. The baseline V1 layout was stored without a version.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum StateVersion {
+    V1,
+    V2,
+}
+
+pub const CURRENT_STATE_VERSION: StateVersion = StateVersion::V2;
+
+pub(crate) fn read_state_version() -> StateVersion {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|bytes| StateVersion::try_from_slice(&bytes).expect("ERR_INVALID_STATE_VERSION"))
+        .unwrap_or(StateVersion::V1)
+}
+
+pub(crate) fn write_state_version(version: StateVersion) {
+    env::storage_write(STATE_VERSION_KEY, &version.try_to_vec().unwrap());
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    owner: AccountId,
+    token: AccountId,
+    records: LookupMap<AccountId, RewardsV1>,
+    deposited_amount: u128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RewardsV1 {
+    rewards: Vector<RewardV1>,
+    amount: u128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RewardV1 {
+    amount: u128,
+    memo: String,
+}
+
+/// History of a V1 record, read in place under its V1 prefix. It holds the first reward indexes of the upgraded record.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyRewards {
+    token_id: AccountId,
+    rewards: Vector<RewardV1>,
+    /// Reason and revoked amount of the revoked V1 rewards.
+    revocations: HashMap<u64, (String, u128)>,
+}
+
+impl RewardsV1 {
+    pub fn get_amount(&self) -> u128 {
+        self.amount
+    }
+
+    /// The record in the current layout, keeping the V1 history where it is stored.
+    /// V1 rewards were always paid in the default `token_id`.
+    pub fn into_rewards(self, account_id: &AccountId, token_id: &AccountId) -> Rewards {
+        let mut current_rewards = Rewards::new(account_id.clone());
+        current_rewards.internal_set_reward_amount(token_id, self.amount);
+        current_rewards.internal_set_legacy_rewards(LegacyRewards {
+            token_id: token_id.clone(),
+            rewards: self.rewards,
+            revocations: HashMap::new(),
+        });
+        current_rewards
+    }
+}
+
+impl LegacyRewards {
+    pub fn get_rewards_len(&self) -> u64 {
+        self.rewards.len()
+    }
+
+    pub fn get_reward(&self, reward_index: u64) -> Reward {
+        let reward = self.rewards.get(reward_index).expect("ERR_NO_REWARD");
+        let mut current_reward = Reward::new_legacy(self.token_id.clone(), reward.amount, reward.memo);
+        if let Some((reason, revoked_amount)) = self.revocations.get(&reward_index) {
+            current_reward.internal_revoke(reason.clone(), *revoked_amount);
+        }
+        current_reward
+    }
+
+    pub fn internal_revoke_reward(&mut self, reward_index: u64, reason: String, revoked_amount: u128) {
+        self.revocations.insert(reward_index, (reason, revoked_amount));
+    }
+}
+
+impl Contract {
+    /// Moves the V1 record of `account_id` into `records`. Only the balance moves, the V1 history is not copied,
+    /// so the upgrade costs the same for any history length.
+    pub(crate) fn internal_upgrade_legacy_rewards(&mut self, account_id: &AccountId) -> Option<Rewards> {
+        let legacy_rewards = self.legacy_records.remove(account_id)?;
+        let token_id = self.token.clone();
+        let amount = legacy_rewards.get_amount();
+        let current_rewards = legacy_rewards.into_rewards(account_id, &token_id);
+        self.records.insert(account_id, &current_rewards);
+        // V1 totals were never tracked, the record is counted when it is upgraded
+        self.num_reward_accounts += 1;
+        self.internal_update_stats(&token_id, |stats| stats.push(amount));
+        Some(current_rewards)
+    }
+
+    /// Rewards of `account_id`, upgrading a V1 record first.
+    /// The caller charges the storage of the upgraded record.
+    pub(crate) fn internal_get_rewards(&mut self, account_id: &AccountId) -> Option<Rewards> {
+        self.records.get(account_id).or_else(|| self.internal_upgrade_legacy_rewards(account_id))
+    }
+
+    /// Rewards of `account_id` for the views, a V1 record is read as if it was upgraded.
+    pub(crate) fn internal_view_rewards(&self, account_id: &AccountId) -> Option<Rewards> {
+        self.records.get(account_id).or_else(|| {
+            self.legacy_records.get(account_id)
+                .map(|legacy_rewards| legacy_rewards.into_rewards(account_id, &self.token))
+        })
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let contract = match read_state_version() {
+            StateVersion::V1 => {
+                let old: ContractV1 = env::state_read().expect("ERR_NOT_INITIALIZED");
+                let mut contract = Self {
+                    owner: old.owner,
+                    token: old.token.clone(),
+                    records: LookupMap::new(b"e".to_vec()),
+                    legacy_records: old.records,
+                    deposited_amount: LookupMap::new(b"d".to_vec()),
+                    storage_deposits: LookupMap::new(b"s".to_vec()),
+                    whitelisted_tokens: UnorderedSet::new(b"w".to_vec()),
+                    pending_owner: None,
+                    roles: LookupMap::new(b"r".to_vec()),
+                    paused_operations: Vec::new(),
+                    airdrops: LookupMap::new(b"a".to_vec()),
+                    next_airdrop_id: 0,
+                    voucher_signer: None,
+                    used_vouchers: LookupSet::new(b"n".to_vec()),
+                    stats: LookupMap::new(b"x".to_vec()),
+                    num_reward_accounts: 0,
+                    next_reward_id: 0,
+                    applied_keys: LookupSet::new(b"k".to_vec()),
+                    campaigns: LookupMap::new(b"c".to_vec()),
+                    next_campaign_id: 0,
+                    funder_balances: LookupMap::new(b"f".to_vec()),
+                };
+                contract.whitelisted_tokens.insert(&old.token);
+                contract.deposited_amount.insert(&old.token, &old.deposited_amount);
+                contract
+            }
+            StateVersion::V2 => env::state_read().expect("ERR_NOT_INITIALIZED"),
+        };
+        write_state_version(CURRENT_STATE_VERSION);
+        contract
+    }
+
+    /// Upgrades the records of `account_ids` ahead of their next push or claim.
+    /// The storage of the upgraded records is paid by the attached deposit, then the owner storage pool.
+    #[payable]
+    pub fn migrate_records(&mut self, account_ids: Vec<ValidAccountId>) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        let initial_storage_usage = env::storage_usage();
+        for account_id in account_ids {
+            self.internal_get_rewards(account_id.as_ref());
+        }
+        self.internal_charge_storage(None, initial_storage_usage);
+    }
+
+    pub fn get_version(&self) -> StateVersion {
+        read_state_version()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::serde_json::{self, Value};
+    use near_sdk::{testing_env};
+
+    const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+
+    fn write_v1_state() {
+        let mut records: LookupMap<AccountId, RewardsV1> = LookupMap::new(b"t".to_vec());
+        for (account_id, amounts) in vec![(accounts(3), vec![10, 20]), (accounts(4), vec![5])] {
+            let mut rewards = RewardsV1 {
+                rewards: Vector::new(account_id.as_ref().as_bytes().to_vec()),
+                amount: 0,
+            };
+            for (index, amount) in amounts.into_iter().enumerate() {
+                rewards.rewards.push(&RewardV1 { amount, memo: format!("reward {}", index) });
+                rewards.amount += amount;
+            }
+            records.insert(account_id.as_ref(), &rewards);
+        }
+        env::state_write(&ContractV1 {
+            owner: accounts(1).into(),
+            token: accounts(2).into(),
+            records,
+            deposited_amount: 100,
+        });
+    }
+
+    #[test]
+    fn test_migrate_from_v1() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        write_v1_state();
+        assert_eq!(read_state_version(), StateVersion::V1);
+
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.get_version(), StateVersion::V2);
+        assert_eq!(contract.get_owner(), accounts(1).to_string());
+        assert_eq!(contract.get_whitelisted_tokens(), vec![accounts(2).to_string()]);
+        assert_eq!(contract.deposited_amount.get(&accounts(2).to_string()), Some(100));
+
+        // legacy records are readable before they are upgraded
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(30));
+        assert_eq!(contract.get_rewards(0, 10, accounts(3), None, None).total_count, 2);
+
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(1)
+                .build());
+        contract.migrate_records(vec![accounts(3), accounts(4)]);
+        assert!(!contract.legacy_records.contains_key(accounts(3).as_ref()));
+        let rewards = contract.records.get(accounts(3).as_ref()).unwrap();
+        assert_eq!(rewards.get_rewards_len(), 2);
+        assert_eq!(rewards.get_reward(1).get_amount(), 20);
+        assert_eq!(rewards.get_reward(1).get_memo(), "reward 1");
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(30));
+        assert_eq!(contract.get_reward_amount(accounts(4), None), U128(5));
+    }
+
+    #[test]
+    fn test_upgraded_record_keeps_v1_history() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        write_v1_state();
+        let mut contract = Contract::migrate();
+        testing_env!(context
+                .predecessor_account_id(accounts(1))
+                .attached_deposit(ONE_NEAR)
+                .build());
+        contract.storage_deposit(None, None);
+
+        testing_env!(context
+                .attached_deposit(1)
+                .block_timestamp(100)
+                .build());
+        contract.push_reward(accounts(3), U128(7), "new reward".to_string(), None, None, None, None);
+        // the V1 history is read in place, not copied
+        assert!(env::storage_has_key(&[accounts(3).as_ref().as_bytes(), &1u64.to_le_bytes()].concat()));
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(37));
+        let page = serde_json::to_value(contract.get_rewards(0, 10, accounts(3), None, None)).unwrap();
+        assert_eq!(page["total_count"], 3);
+        assert_eq!(page["rewards"][0]["memo"], "new reward");
+        assert_eq!(page["rewards"][0]["id"], "0");
+        assert_eq!(page["rewards"][2]["memo"], "reward 0");
+        assert_eq!(page["rewards"][2]["id"], Value::Null);
+        assert_eq!(contract.get_rewards(0, 10, accounts(3), Some(U64(1)), None).total_count, 1);
+
+        let revoked = contract.revoke_reward(accounts(3), 1, "wrong account".to_string());
+        assert_eq!(revoked, U128(20));
+        let page = serde_json::to_value(contract.get_rewards(0, 10, accounts(3), None, None)).unwrap();
+        assert_eq!(page["rewards"][1]["revoked_reason"], "wrong account");
+        assert_eq!(contract.get_reward_amount(accounts(3), None), U128(17));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum Role {
+    /// Allowed to push rewards, but not to withdraw deposits or reconfigure the contract.
+    Distributor,
+    /// Allowed to deposit into their own pool and push rewards from it.
+    Funder,
+}
+
+impl Contract {
+    /// Owner and distributors can push rewards.
+    pub(crate) fn assert_distributor(&self) {
+        let predecessor_id = env::predecessor_account_id();
+        assert!(
+            predecessor_id == self.owner || self.internal_has_role(&predecessor_id, Role::Distributor),
+            "ERR_NOT_DISTRIBUTOR"
+        );
+    }
+
+    pub(crate) fn assert_funder(&self) {
+        assert!(self.internal_has_role(&env::predecessor_account_id(), Role::Funder), "ERR_NOT_FUNDER");
+    }
+
+    pub(crate) fn internal_has_role(&self, account_id: &AccountId, role: Role) -> bool {
+        self.roles.get(account_id).map_or(false, |roles| roles.contains(&role))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// First step of the ownership transfer, `new_owner` has to call `accept_ownership`.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner: ValidAccountId) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        self.pending_owner = Some(new_owner.into());
+    }
+
+    #[payable]
+    pub fn accept_ownership(&mut self) {
+        assert_one_yocto();
+        let pending_owner = self.pending_owner.take().expect("ERR_NO_PENDING_OWNER");
+        assert_eq!(pending_owner, env::predecessor_account_id(), "ERR_NOT_PENDING_OWNER");
+        log!("Ownership transferred from {} to {}", self.owner, pending_owner);
+        self.owner = pending_owner;
+    }
+
+    #[payable]
+    pub fn grant_role(&mut self, account_id: ValidAccountId, role: Role) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let mut roles = self.roles.get(account_id.as_ref()).unwrap_or_default();
+        assert!(!roles.contains(&role), "ERR_ROLE_ALREADY_GRANTED");
+        roles.push(role);
+        self.roles.insert(account_id.as_ref(), &roles);
+    }
+
+    #[payable]
+    pub fn revoke_role(&mut self, account_id: ValidAccountId, role: Role) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let mut roles = self.roles.get(account_id.as_ref()).expect("ERR_ROLE_NOT_GRANTED");
+        assert!(roles.contains(&role), "ERR_ROLE_NOT_GRANTED");
+        roles.retain(|r| *r != role);
+        if roles.is_empty() {
+            self.roles.remove(account_id.as_ref());
+        } else {
+            self.roles.insert(account_id.as_ref(), &roles);
+        }
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner.clone()
+    }
+
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner.clone()
+    }
+
+    pub fn get_roles(&self, account_id: ValidAccountId) -> Vec<Role> {
+        self.roles.get(account_id.as_ref()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,52 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum Operation {
+    Deposit,
+    Push,
+    Claim,
+}
+
+const ALL_OPERATIONS: [Operation; 3] = [Operation::Deposit, Operation::Push, Operation::Claim];
+
+impl Contract {
+    pub(crate) fn assert_not_paused(&self, operation: Operation) {
+        assert!(!self.paused_operations.contains(&operation), "ERR_PAUSED");
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pauses `operation`, or every operation if none is given.
+    #[payable]
+    pub fn pause(&mut self, operation: Option<Operation>) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let operations = operation.map_or(ALL_OPERATIONS.to_vec(), |operation| vec![operation]);
+        for operation in operations {
+            if !self.paused_operations.contains(&operation) {
+                self.paused_operations.push(operation);
+            }
+        }
+    }
+
+    /// Unpauses `operation`, or every operation if none is given.
+    #[payable]
+    pub fn unpause(&mut self, operation: Option<Operation>) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        match operation {
+            Some(operation) => self.paused_operations.retain(|paused| *paused != operation),
+            None => self.paused_operations.clear(),
+        }
+    }
+
+    /// Whether `operation` is paused, or whether the whole contract is paused if none is given.
+    pub fn is_paused(&self, operation: Option<Operation>) -> bool {
+        match operation {
+            Some(operation) => self.paused_operations.contains(&operation),
+            None => ALL_OPERATIONS.iter().all(|operation| self.paused_operations.contains(operation)),
+        }
+    }
+}
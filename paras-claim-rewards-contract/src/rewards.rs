@@ -1,87 +1,599 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::{AccountId};
-use near_sdk::json_types::{U128};
+use near_sdk::{env, AccountId};
+use near_sdk::json_types::{U128, U64};
 use near_sdk::collections::{Vector};
 use near_sdk::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::migration::LegacyRewards;
 
+/// First byte of every reward history prefix, no other collection of the contract starts with it.
+const REWARDS_PREFIX_TAG: u8 = b'h';
+
+/// Storage prefix of the reward history of `account_id`.
+/// The account id is hashed, so no account id can produce keys overlapping another history or map.
+pub fn rewards_prefix(account_id: &AccountId) -> Vec<u8> {
+    let mut prefix = vec![REWARDS_PREFIX_TAG];
+    prefix.extend(env::sha256(account_id.as_bytes()));
+    prefix
+}
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Reward {
+    /// Global id, increasing with every pushed reward of the contract. `None` for V1 rewards.
+    id: Option<u64>,
+    token_id: AccountId,
     amount: u128,
     memo: String,
+    expires_at: Option<u64>,
+    /// Block timestamp of the push, 0 for V1 rewards.
+    created_at: u64,
+    /// Block height of the push, 0 for V1 rewards.
+    block_height: u64,
+    /// `None` for V1 rewards.
+    pushed_by: Option<AccountId>,
+    /// Where the amount was taken from, `Deposit` for V1 rewards.
+    source: RewardSource,
+    /// Set when the owner revoked the reward.
+    revoked_reason: Option<String>,
+    /// Unclaimed part of the reward taken back by the revocation.
+    revoked_amount: u128,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct WrappedReward {
+    id: Option<U64>,
+    token_id: AccountId,
+    amount: U128,
+    memo: String,
+    expires_at: Option<U64>,
+    created_at: U64,
+    block_height: U64,
+    pushed_by: Option<AccountId>,
+    campaign_id: Option<u64>,
+    funded_by: Option<AccountId>,
+    revoked_reason: Option<String>,
+    revoked_amount: U128,
+}
+
+/// Where the amount of a reward is taken from, and where its revoked or expired part goes back to.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+pub enum RewardSource {
+    Deposit,
+    Campaign(u64),
+    Funder(AccountId),
+}
+
+/// A page of rewards, newest first.
+#[derive(Deserialize, Serialize)]
+pub struct WrappedRewards {
+    pub rewards: Vec<WrappedReward>,
+    /// Number of rewards of the account across all pages.
+    pub total_count: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum ClaimStatus {
+    /// Waiting for the transfer callback.
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+/// Outgoing claim of an account.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Claim {
+    token_id: AccountId,
+    amount: u128,
+    receiver_id: AccountId,
+    created_at: u64,
+    status: ClaimStatus,
+    /// Part of `amount` credited back to the balance, the whole amount when the transfer failed.
+    refunded_amount: u128,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WrappedClaim {
+    token_id: AccountId,
     amount: U128,
-    memo: String
+    receiver_id: AccountId,
+    created_at: U64,
+    status: ClaimStatus,
+    refunded_amount: U128,
+}
+
+/// A page of claims, newest first.
+#[derive(Deserialize, Serialize)]
+pub struct WrappedClaims {
+    pub claims: Vec<WrappedClaim>,
+    /// Number of claims of the account across all pages.
+    pub total_count: u64,
+}
+
+/// Unclaimed part of a reward that can no longer be claimed after `expires_at`.
+/// Also passed to the claim callbacks, to give a refunded claim its expiry back.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+pub struct ExpiringAmount {
+    token_id: AccountId,
+    amount: u128,
+    expires_at: u64,
+    source: RewardSource,
+}
+
+/// Linear unlock of a pushed reward. Nothing is unlocked before `start + cliff`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Vesting {
+    reward_index: u64,
+    token_id: AccountId,
+    amount: u128,
+    start: u64,
+    cliff: u64,
+    duration: u64,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct WrappedVesting {
+    reward_index: u64,
+    token_id: AccountId,
+    amount: U128,
+    start: U64,
+    cliff: U64,
+    duration: U64,
+    vested_amount: U128,
+}
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Rewards {
+    /// V1 history of an upgraded V1 record, at the reward indexes before `rewards`.
+    legacy_rewards: Option<LegacyRewards>,
     rewards: Vector<Reward>,
-    amount: u128,
+    claims: Vector<Claim>,
+    amounts: HashMap<AccountId, u128>,
+    expiring_amounts: Vec<ExpiringAmount>,
+    vestings: Vec<Vesting>,
 }
 
 impl Rewards{
     pub fn new(account_id: AccountId) -> Self {
         Self {
-            rewards: Vector::new(account_id.as_bytes().to_vec()),
-            amount: 0,
+            legacy_rewards: None,
+            rewards: Vector::new(rewards_prefix(&account_id)),
+            claims: Vector::new([rewards_prefix(&account_id), b"c".to_vec()].concat()),
+            amounts: HashMap::new(),
+            expiring_amounts: Vec::new(),
+            vestings: Vec::new(),
         }
     }
     
+    pub fn internal_set_legacy_rewards(&mut self, legacy_rewards: LegacyRewards) {
+        self.legacy_rewards = Some(legacy_rewards);
+    }
+
+    fn get_legacy_rewards_len(&self) -> u64 {
+        self.legacy_rewards.as_ref().map_or(0, |legacy_rewards| legacy_rewards.get_rewards_len())
+    }
+
     pub fn internal_add_new_reward(&mut self, reward: Reward) {
         self.rewards.push(&reward);
     }
 
-    pub fn internal_set_reward_amount(&mut self, amount: u128) {
-        self.amount = amount;
+    pub fn internal_set_reward_amount(&mut self, token_id: &AccountId, amount: u128) {
+        if amount == 0 {
+            self.amounts.remove(token_id);
+        } else {
+            self.amounts.insert(token_id.clone(), amount);
+        }
+    }
+
+    pub fn internal_reward_amount(&self, token_id: &AccountId) -> u128 {
+        return self.amounts.get(token_id).cloned().unwrap_or(0);
+    }
+
+    /// Reward amount minus the expired, not yet swept amounts and the still locked vesting amounts.
+    pub fn internal_claimable_amount(&self, token_id: &AccountId, now: u64) -> u128 {
+        let expired_amount: u128 = self.expiring_amounts.iter()
+            .filter(|expiring| &expiring.token_id == token_id && expiring.expires_at <= now)
+            .map(|expiring| expiring.amount)
+            .sum();
+        let unvested_amount: u128 = self.vestings.iter()
+            .filter(|vesting| &vesting.token_id == token_id)
+            .map(|vesting| vesting.internal_unvested_amount(now))
+            .sum();
+        self.internal_reward_amount(token_id)
+            .saturating_sub(expired_amount)
+            .saturating_sub(unvested_amount)
+    }
+
+    pub fn internal_add_vesting(&mut self, vesting: Vesting) {
+        self.vestings.push(vesting);
+    }
+
+    /// Drops fully vested schedules, their amounts are plain reward amounts from now on.
+    pub fn internal_release_vested(&mut self, now: u64) {
+        self.vestings.retain(|vesting| vesting.internal_unvested_amount(now) > 0);
+    }
+
+    /// Removes the vesting of `reward_index` and its unvested amount from the reward amounts.
+    /// Returns the token and the removed unvested amount.
+    pub fn internal_revoke_vesting(&mut self, reward_index: u64, now: u64) -> (AccountId, u128) {
+        let position = self.vestings.iter()
+            .position(|vesting| vesting.reward_index == reward_index)
+            .expect("ERR_NO_VESTING");
+        let vesting = self.vestings.remove(position);
+        let unvested_amount = vesting.internal_unvested_amount(now);
+        let current_amount = self.internal_reward_amount(&vesting.token_id);
+        self.internal_set_reward_amount(&vesting.token_id, current_amount.checked_sub(unvested_amount).expect("ERR_INTEGER_OVERFLOW"));
+        (vesting.token_id, unvested_amount)
+    }
+
+    /// Takes the unclaimed part of the reward at `reward_index` out of the reward amounts and marks the reward revoked.
+    /// Claims draw from expiring amounts first, so a non-expiring reward is unclaimed up to the amount not expiring nor vesting.
+    /// Returns the token and the revoked amount.
+    pub fn internal_revoke_reward(&mut self, reward_index: u64, reason: String, now: u64) -> (AccountId, u128) {
+        let mut reward = self.get_reward(reward_index);
+        assert!(reward.revoked_reason.is_none(), "ERR_REWARD_REVOKED");
+        assert!(
+            self.vestings.iter().all(|vesting| vesting.reward_index != reward_index),
+            "ERR_REWARD_IS_VESTING"
+        );
+        let token_id = reward.token_id.clone();
+        let current_amount = self.internal_reward_amount(&token_id);
+        let revoked_amount = match reward.expires_at {
+            Some(expires_at) => {
+                assert!(expires_at > now, "ERR_REWARD_EXPIRED");
+                let mut remaining = reward.amount;
+                for expiring in self.expiring_amounts.iter_mut()
+                    .filter(|expiring| expiring.token_id == token_id && expiring.expires_at == expires_at && expiring.source == reward.source) {
+                    let used = std::cmp::min(expiring.amount, remaining);
+                    expiring.amount -= used;
+                    remaining -= used;
+                }
+                self.expiring_amounts.retain(|expiring| expiring.amount > 0);
+                reward.amount - remaining
+            }
+            None => {
+                let expiring_amount: u128 = self.expiring_amounts.iter()
+                    .filter(|expiring| expiring.token_id == token_id)
+                    .map(|expiring| expiring.amount)
+                    .sum();
+                let unvested_amount: u128 = self.vestings.iter()
+                    .filter(|vesting| vesting.token_id == token_id)
+                    .map(|vesting| vesting.internal_unvested_amount(now))
+                    .sum();
+                std::cmp::min(reward.amount, current_amount.saturating_sub(expiring_amount).saturating_sub(unvested_amount))
+            }
+        };
+        assert!(revoked_amount > 0, "ERR_REWARD_ALREADY_CLAIMED");
+        self.internal_set_reward_amount(&token_id, current_amount - revoked_amount);
+        let legacy_rewards_len = self.get_legacy_rewards_len();
+        if reward_index < legacy_rewards_len {
+            self.legacy_rewards.as_mut().unwrap().internal_revoke_reward(reward_index, reason, revoked_amount);
+        } else {
+            reward.internal_revoke(reason, revoked_amount);
+            self.rewards.replace(reward_index - legacy_rewards_len, &reward);
+        }
+        (token_id, revoked_amount)
+    }
+
+    pub fn get_vestings(&self, now: u64) -> Vec<WrappedVesting> {
+        self.vestings.iter().map(|vesting| vesting.to_wvesting(now)).collect()
+    }
+
+    pub fn internal_add_expiring_amount(&mut self, token_id: &AccountId, amount: u128, expires_at: u64, source: RewardSource) {
+        self.expiring_amounts.push(ExpiringAmount {
+            token_id: token_id.clone(),
+            amount,
+            expires_at,
+            source,
+        });
+    }
+
+    /// Claims draw from the soonest expiring amounts first. Returns the used expiring amounts.
+    pub fn internal_use_expiring_amount(&mut self, token_id: &AccountId, amount: u128, now: u64) -> Vec<ExpiringAmount> {
+        let mut used_amounts = Vec::new();
+        let mut remaining = amount;
+        self.expiring_amounts.sort_by_key(|expiring| expiring.expires_at);
+        for expiring in self.expiring_amounts.iter_mut()
+            .filter(|expiring| &expiring.token_id == token_id && expiring.expires_at > now) {
+            if remaining == 0 {
+                break;
+            }
+            let used = std::cmp::min(expiring.amount, remaining);
+            expiring.amount -= used;
+            remaining -= used;
+            used_amounts.push(ExpiringAmount {
+                token_id: token_id.clone(),
+                amount: used,
+                expires_at: expiring.expires_at,
+                source: expiring.source.clone(),
+            });
+        }
+        self.expiring_amounts.retain(|expiring| expiring.amount > 0);
+        used_amounts
     }
 
-    pub fn internal_reward_amount(&self) -> u128 {
-        return self.amount;
+    /// Puts back up to `refunded_amount` of the expiring amounts used by a claim, latest expiring first,
+    /// as the claimed tokens are taken from the soonest expiring ones. Expired amounts become sweepable.
+    pub fn internal_restore_expiring_amounts(&mut self, mut used_amounts: Vec<ExpiringAmount>, refunded_amount: u128) {
+        let mut remaining = refunded_amount;
+        used_amounts.sort_by_key(|expiring| std::cmp::Reverse(expiring.expires_at));
+        for mut expiring in used_amounts {
+            if remaining == 0 {
+                break;
+            }
+            expiring.amount = std::cmp::min(expiring.amount, remaining);
+            remaining -= expiring.amount;
+            self.expiring_amounts.push(expiring);
+        }
     }
 
-    pub fn get_reward(&self, reward_id: u64) -> Reward {
-        self.rewards.get(reward_id).expect("ERR_NO_REWARD").into()
+    /// Removes expired amounts from the reward amounts. Returns the removed amount per token and source.
+    pub fn internal_take_expired_amounts(&mut self, now: u64) -> HashMap<(AccountId, RewardSource), u128> {
+        let mut expired_amounts: HashMap<(AccountId, RewardSource), u128> = HashMap::new();
+        for expiring in self.expiring_amounts.iter().filter(|expiring| expiring.expires_at <= now) {
+            *expired_amounts.entry((expiring.token_id.clone(), expiring.source.clone())).or_insert(0) += expiring.amount;
+        }
+        self.expiring_amounts.retain(|expiring| expiring.expires_at > now);
+        for ((token_id, _), expired_amount) in expired_amounts.iter() {
+            let current_amount = self.internal_reward_amount(token_id);
+            self.internal_set_reward_amount(token_id, current_amount.checked_sub(*expired_amount).expect("ERR_INTEGER_OVERFLOW"));
+        }
+        expired_amounts
+    }
+
+    pub fn get_reward(&self, reward_index: u64) -> Reward {
+        let legacy_rewards_len = self.get_legacy_rewards_len();
+        if reward_index < legacy_rewards_len {
+            return self.legacy_rewards.as_ref().unwrap().get_reward(reward_index);
+        }
+        self.rewards.get(reward_index - legacy_rewards_len).expect("ERR_NO_REWARD")
     }
 
     pub fn get_rewards_len(&self) -> u64 {
-        self.rewards.len()
+        self.get_legacy_rewards_len() + self.rewards.len()
+    }
+
+    /// Records a pending claim. Returns its index in the claim history.
+    pub fn internal_add_claim(&mut self, token_id: &AccountId, amount: u128, receiver_id: &AccountId) -> u64 {
+        self.claims.push(&Claim {
+            token_id: token_id.clone(),
+            amount,
+            receiver_id: receiver_id.clone(),
+            created_at: env::block_timestamp(),
+            status: ClaimStatus::Pending,
+            refunded_amount: 0,
+        });
+        self.claims.len() - 1
+    }
+
+    pub fn internal_resolve_claim(&mut self, claim_index: u64, status: ClaimStatus, refunded_amount: u128) {
+        let mut claim = self.claims.get(claim_index).expect("ERR_NO_CLAIM");
+        claim.status = status;
+        claim.refunded_amount = refunded_amount;
+        self.claims.replace(claim_index, &claim);
+    }
+
+    pub fn get_claim(&self, claim_index: u64) -> WrappedClaim {
+        let claim = self.claims.get(claim_index).expect("ERR_NO_CLAIM");
+        WrappedClaim {
+            token_id: claim.token_id,
+            amount: claim.amount.into(),
+            receiver_id: claim.receiver_id,
+            created_at: claim.created_at.into(),
+            status: claim.status,
+            refunded_amount: claim.refunded_amount.into(),
+        }
+    }
+
+    pub fn get_claims_len(&self) -> u64 {
+        self.claims.len()
+    }
+
+    /// Index of the first reward created at or after `timestamp`. Rewards are stored in creation order,
+    /// V1 rewards first with `created_at` 0.
+    pub fn internal_index_from(&self, timestamp: u64) -> u64 {
+        let legacy_rewards_len = self.get_legacy_rewards_len();
+        if timestamp == 0 {
+            return 0;
+        }
+        let (mut low, mut high) = (legacy_rewards_len, self.get_rewards_len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.rewards.get(mid - legacy_rewards_len).unwrap().created_at < timestamp {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
     }
 }
 
 impl Reward {
+    /// Reward pushed in the current block by `pushed_by`.
     pub fn new(
+        id: u64,
+        token_id: AccountId,
         amount: U128,
         memo: String,
+        expires_at: Option<u64>,
+        pushed_by: AccountId,
+        source: RewardSource,
     ) -> Self {
         Self {
+            id: Some(id),
+            token_id,
             amount: amount.into(),
-            memo: memo,
+            memo,
+            expires_at,
+            created_at: env::block_timestamp(),
+            block_height: env::block_index(),
+            pushed_by: Some(pushed_by),
+            source,
+            revoked_reason: None,
+            revoked_amount: 0,
+        }
+    }
+
+    /// Reward from a V1 record, which kept neither the time nor the pusher.
+    pub fn new_legacy(token_id: AccountId, amount: u128, memo: String) -> Self {
+        Self {
+            id: None,
+            token_id,
+            amount,
+            memo,
+            expires_at: None,
+            created_at: 0,
+            block_height: 0,
+            pushed_by: None,
+            source: RewardSource::Deposit,
+            revoked_reason: None,
+            revoked_amount: 0,
         }
     }
+    pub fn get_token_id(&self) -> AccountId {
+        self.token_id.clone()
+    }
     pub fn get_amount(&self) -> u128 {
         self.amount
     }
     pub fn get_memo(&self) -> String {
         self.memo.clone()
     }
+    pub fn get_expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+    pub fn get_source(&self) -> RewardSource {
+        self.source.clone()
+    }
+    /// Funder whose pool paid the reward, `None` when paid from the deposit or a campaign budget.
+    pub fn get_funded_by(&self) -> Option<AccountId> {
+        match &self.source {
+            RewardSource::Funder(funder_id) => Some(funder_id.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn internal_revoke(&mut self, reason: String, revoked_amount: u128) {
+        self.revoked_reason = Some(reason);
+        self.revoked_amount = revoked_amount;
+    }
 
     pub fn to_wreward(&self) -> WrappedReward {
         WrappedReward::new(self)
     }
 }
 
+impl Vesting {
+    pub fn new(
+        reward_index: u64,
+        token_id: AccountId,
+        amount: u128,
+        start: u64,
+        cliff: u64,
+        duration: u64,
+    ) -> Self {
+        assert!(duration > 0, "ERR_INVALID_VESTING_DURATION");
+        assert!(cliff <= duration, "ERR_INVALID_VESTING_CLIFF");
+        Self {
+            reward_index,
+            token_id,
+            amount,
+            start,
+            cliff,
+            duration,
+        }
+    }
+
+    pub fn internal_vested_amount(&self, now: u64) -> u128 {
+        if now < self.start.saturating_add(self.cliff) {
+            return 0;
+        }
+        let elapsed = now - self.start;
+        if elapsed >= self.duration {
+            return self.amount;
+        }
+        // amount * elapsed / duration without overflowing u128
+        let (elapsed, duration) = (elapsed as u128, self.duration as u128);
+        self.amount / duration * elapsed + self.amount % duration * elapsed / duration
+    }
+
+    pub fn internal_unvested_amount(&self, now: u64) -> u128 {
+        self.amount - self.internal_vested_amount(now)
+    }
+
+    pub fn to_wvesting(&self, now: u64) -> WrappedVesting {
+        WrappedVesting {
+            reward_index: self.reward_index,
+            token_id: self.token_id.clone(),
+            amount: self.amount.into(),
+            start: self.start.into(),
+            cliff: self.cliff.into(),
+            duration: self.duration.into(),
+            vested_amount: self.internal_vested_amount(now).into(),
+        }
+    }
+}
+
 impl WrappedReward {
     pub fn new(
         reward: &Reward
     ) -> Self {
         Self {
+            id: reward.id.map(|id| id.into()),
+            token_id: reward.get_token_id(),
             amount: reward.get_amount().into(),
-            memo: reward.get_memo()
+            memo: reward.get_memo(),
+            expires_at: reward.get_expires_at().map(|expires_at| expires_at.into()),
+            created_at: reward.created_at.into(),
+            block_height: reward.block_height.into(),
+            pushed_by: reward.pushed_by.clone(),
+            campaign_id: match reward.source {
+                RewardSource::Campaign(campaign_id) => Some(campaign_id),
+                _ => None,
+            },
+            funded_by: reward.get_funded_by(),
+            revoked_reason: reward.revoked_reason.clone(),
+            revoked_amount: reward.revoked_amount.into(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env};
+
+    fn reward(memo: &str) -> Reward {
+        Reward::new(0, "token".to_string(), U128(1), memo.to_string(), None, "owner".to_string(), RewardSource::Deposit)
+    }
+
+    #[test]
+    fn test_rewards_prefix() {
+        testing_env!(VMContextBuilder::new().build());
+        let prefix = rewards_prefix(&"alice".to_string());
+        assert_eq!(prefix.len(), 33);
+        assert_eq!(prefix[0], REWARDS_PREFIX_TAG);
+        assert_ne!(prefix, rewards_prefix(&"alice.near".to_string()));
+    }
+
+    #[test]
+    fn test_adversarial_account_histories() {
+        testing_env!(VMContextBuilder::new().build());
+        // with raw prefixes "tt" shares the first bytes of every key of "tt.near" and of the `t` records map
+        let mut short_rewards = Rewards::new("tt".to_string());
+        let mut long_rewards = Rewards::new("tt.near".to_string());
+        short_rewards.internal_add_new_reward(reward("short 0"));
+        long_rewards.internal_add_new_reward(reward("long 0"));
+        long_rewards.internal_add_new_reward(reward("long 1"));
+        short_rewards.internal_add_new_reward(reward("short 1"));
+
+        assert_eq!(short_rewards.get_rewards_len(), 2);
+        assert_eq!(long_rewards.get_rewards_len(), 2);
+        assert_eq!(short_rewards.get_reward(0).get_memo(), "short 0");
+        assert_eq!(short_rewards.get_reward(1).get_memo(), "short 1");
+        assert_eq!(long_rewards.get_reward(0).get_memo(), "long 0");
+        assert_eq!(long_rewards.get_reward(1).get_memo(), "long 1");
+        assert!(!env::storage_has_key(b"tt\x00\x00\x00\x00\x00\x00\x00\x00"));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Running totals of a token.
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct Stats {
+    total_pushed: u128,
+    total_claimed: u128,
+    /// Pushed but not yet claimed, swept or revoked.
+    total_outstanding: u128,
+    /// Funded to campaigns and not yet pushed nor returned to the deposit.
+    total_campaign_budget: u128,
+    /// Held in funder pools.
+    total_funder_balance: u128,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WrappedStats {
+    token_id: AccountId,
+    total_pushed: U128,
+    total_claimed: U128,
+    total_outstanding: U128,
+    total_campaign_budget: U128,
+    total_funder_balance: U128,
+    deposited_amount: U128,
+    num_reward_accounts: u64,
+}
+
+impl Stats {
+    pub fn push(&mut self, amount: u128) {
+        self.total_pushed = self.total_pushed.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+        self.total_outstanding = self.total_outstanding.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+
+    /// Undoes a push whose amount went back to the deposit without being claimed.
+    pub fn cancel_push(&mut self, amount: u128) {
+        self.total_pushed = self.total_pushed.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW");
+        self.release(amount);
+    }
+
+    pub fn claim(&mut self, amount: u128) {
+        self.total_claimed = self.total_claimed.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+        self.total_outstanding = self.total_outstanding.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+
+    /// Undoes a claim whose transfer failed or was refunded.
+    pub fn refund_claim(&mut self, amount: u128) {
+        self.total_claimed = self.total_claimed.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW");
+        self.total_outstanding = self.total_outstanding.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+
+    /// Outstanding amount returned to the deposit (swept, revoked or closed).
+    pub fn release(&mut self, amount: u128) {
+        self.total_outstanding = self.total_outstanding.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+
+    pub fn add_campaign_budget(&mut self, amount: u128) {
+        self.total_campaign_budget = self.total_campaign_budget.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+
+    /// Campaign budget pushed or returned to the deposit.
+    pub fn remove_campaign_budget(&mut self, amount: u128) {
+        self.total_campaign_budget = self.total_campaign_budget.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+
+    pub fn add_funder_balance(&mut self, amount: u128) {
+        self.total_funder_balance = self.total_funder_balance.checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+
+    /// Funder balance pushed or withdrawn.
+    pub fn remove_funder_balance(&mut self, amount: u128) {
+        self.total_funder_balance = self.total_funder_balance.checked_sub(amount).expect("ERR_INTEGER_OVERFLOW");
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_update_stats<F: FnOnce(&mut Stats)>(&mut self, token_id: &AccountId, f: F) {
+        let mut stats = self.stats.get(token_id).unwrap_or_default();
+        f(&mut stats);
+        self.stats.insert(token_id, &stats);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_stats(&self, token_id: Option<ValidAccountId>) -> WrappedStats {
+        let token_id = self.internal_token_id(token_id);
+        let stats = self.stats.get(&token_id).unwrap_or_default();
+        WrappedStats {
+            deposited_amount: self.internal_deposited_amount(&token_id).into(),
+            token_id,
+            total_pushed: stats.total_pushed.into(),
+            total_claimed: stats.total_claimed.into(),
+            total_outstanding: stats.total_outstanding.into(),
+            total_campaign_budget: stats.total_campaign_budget.into(),
+            total_funder_balance: stats.total_funder_balance.into(),
+            num_reward_accounts: self.num_reward_accounts,
+        }
+    }
+
+    /// Checks `deposited_amount + total_outstanding + total_campaign_budget + total_funder_balance` against the token balance held by the contract.
+    /// Only holds while no claim or withdrawal is in flight, and once every V1 record is migrated.
+    pub fn audit(&self, token_id: Option<ValidAccountId>) -> Promise {
+        let token_id = self.internal_token_id(token_id);
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            &token_id,
+            0,
+            GAS_FOR_FT_BALANCE_OF
+        ).then(ext_self::on_audit(
+            token_id,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ))
+    }
+
+    /// Returns whether the balance matches the accounted amount.
+    #[private]
+    pub fn on_audit(&self, token_id: AccountId) -> bool {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        let balance: u128 = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value)
+                .expect("ERR_INVALID_BALANCE")
+                .into(),
+            _ => env::panic(b"ERR_BALANCE_UNAVAILABLE"),
+        };
+        let stats = self.stats.get(&token_id).unwrap_or_default();
+        let accounted_amount = self.internal_deposited_amount(&token_id) + stats.total_outstanding
+            + stats.total_campaign_budget + stats.total_funder_balance;
+        log!("Audit {} : balance {} accounted {}", token_id, balance, accounted_amount);
+        balance == accounted_amount
+    }
+}
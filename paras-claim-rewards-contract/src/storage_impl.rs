@@ -0,0 +1,148 @@
+use crate::*;
+use near_sdk::{Balance, StorageUsage};
+
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+
+/// Minimum storage balance, enough to cover one new `Rewards` entry with its first `Reward`.
+pub const MIN_STORAGE_BALANCE: Balance = 2_500_000_000_000_000_000_000;
+
+impl Contract {
+    fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        self.storage_deposits.get(account_id).map(|balance| StorageBalance {
+            total: balance.into(),
+            available: balance.into(),
+        })
+    }
+
+    /// Takes up to `amount` from `account_id` storage balance. Returns the part that could not be covered.
+    fn internal_use_storage_balance(&mut self, account_id: &AccountId, amount: Balance) -> Balance {
+        if amount == 0 {
+            return 0;
+        }
+        match self.storage_deposits.get(account_id) {
+            Some(balance) => {
+                let used = std::cmp::min(balance, amount);
+                self.storage_deposits.insert(account_id, &(balance - used));
+                amount - used
+            }
+            None => amount,
+        }
+    }
+
+    /// Charges the storage added since `initial_storage_usage`.
+    /// Paid from the attached deposit (above the 1 yoctoNEAR), then the recipient storage balance,
+    /// then the owner storage pool. The unused attached deposit is refunded to the caller.
+    pub(crate) fn internal_charge_storage(&mut self, recipient_id: Option<&AccountId>, initial_storage_usage: StorageUsage) {
+        let owner = self.owner.clone();
+        let payer_ids: Vec<&AccountId> = recipient_id.into_iter().chain(std::iter::once(&owner)).collect();
+        self.internal_pay_storage(&payer_ids, initial_storage_usage);
+    }
+
+    /// Charges the storage added since `initial_storage_usage` without falling back to the owner storage pool.
+    /// Paid from the attached deposit (above the 1 yoctoNEAR), then the `account_id` storage balance.
+    pub(crate) fn internal_charge_account_storage(&mut self, account_id: &AccountId, initial_storage_usage: StorageUsage) {
+        self.internal_pay_storage(&[account_id], initial_storage_usage);
+    }
+
+    fn internal_pay_storage(&mut self, payer_ids: &[&AccountId], initial_storage_usage: StorageUsage) {
+        let storage_cost = Balance::from(env::storage_usage().saturating_sub(initial_storage_usage)) * env::storage_byte_cost();
+        let attached_deposit = env::attached_deposit().saturating_sub(1);
+
+        let from_attached = std::cmp::min(storage_cost, attached_deposit);
+        let mut remaining = storage_cost - from_attached;
+        for payer_id in payer_ids {
+            remaining = self.internal_use_storage_balance(payer_id, remaining);
+        }
+        assert_eq!(remaining, 0, "ERR_STORAGE_DEPOSIT_NOT_ENOUGH");
+
+        let refund = attached_deposit - from_attached;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id
+            .map(|a| a.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+        let current_balance = self.storage_deposits.get(&account_id);
+
+        if registration_only {
+            if current_balance.is_some() {
+                log!("The account is already registered, refunding the deposit");
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                assert!(amount >= MIN_STORAGE_BALANCE, "ERR_STORAGE_DEPOSIT_TOO_SMALL");
+                self.storage_deposits.insert(&account_id, &MIN_STORAGE_BALANCE);
+                let refund = amount - MIN_STORAGE_BALANCE;
+                if refund > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(refund);
+                }
+            }
+        } else {
+            assert!(
+                current_balance.is_some() || amount >= MIN_STORAGE_BALANCE,
+                "ERR_STORAGE_DEPOSIT_TOO_SMALL"
+            );
+            let balance = current_balance.unwrap_or(0).checked_add(amount).expect("ERR_INTEGER_OVERFLOW");
+            self.storage_deposits.insert(&account_id, &balance);
+        }
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = self.storage_deposits.get(&account_id).expect("ERR_STORAGE_NOT_REGISTERED");
+        let amount: Balance = amount.map(|a| a.into()).unwrap_or(balance);
+        assert!(amount <= balance, "ERR_STORAGE_WITHDRAW_TOO_MUCH");
+        self.storage_deposits.insert(&account_id, &(balance - amount));
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    /// Paid record storage is never released, so unregistering only refunds the remaining balance.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let _ = force;
+        let account_id = env::predecessor_account_id();
+        match self.storage_deposits.remove(&account_id) {
+            Some(balance) => {
+                if balance > 0 {
+                    Promise::new(account_id).transfer(balance);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: MIN_STORAGE_BALANCE.into(),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance_of(account_id.as_ref())
+    }
+}
@@ -1,11 +1,74 @@
 use crate::*;
-use near_sdk::{PromiseOrValue, log};
+use near_sdk::PromiseOrValue;
+use near_sdk::serde::Deserialize;
+use near_sdk::serde_json;
 
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
 
+#[derive(Deserialize)]
+struct RewardMessage {
+    account_id: ValidAccountId,
+    memo: String,
+    /// Defaults to the whole transferred amount.
+    amount: Option<U128>,
+    expires_at: Option<U64>,
+}
+
+/// Instruction in the `ft_transfer_call` msg. An empty msg adds the tokens to the deposit.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TokenReceiverMessage {
+    /// Adds the tokens to the budget of a campaign.
+    Campaign(u64),
+    /// Adds the tokens to the pool of the sender, who needs the `Funder` role.
+    FunderDeposit {},
+    /// Pushes a reward funded by the transfer, the sender needs the `Funder` role.
+    Reward(RewardMessage),
+    /// Pushes rewards funded by the transfer, each entry needs an `amount`. The sender needs the `Funder` role.
+    Batch(Vec<RewardMessage>),
+}
+
+impl Contract {
+    /// Pushes rewards funded by `sender_id` from a transfer of `amount`, through the sender pool. Returns the unused amount.
+    fn internal_push_funded_rewards(
+        &mut self,
+        token_id: &AccountId,
+        sender_id: &AccountId,
+        amount: u128,
+        rewards: Vec<RewardMessage>,
+    ) -> u128 {
+        self.assert_not_paused(Operation::Push);
+        assert!(!rewards.is_empty(), "ERR_BATCH_EMPTY");
+        assert!(rewards.len() as u64 <= MAX_REWARDS_PER_BATCH, "ERR_BATCH_TOO_LARGE");
+        let used_amount = rewards.iter()
+            .try_fold(0u128, |total, reward| total.checked_add(reward.amount.map(|a| a.0).unwrap_or(amount)))
+            .expect("ERR_INTEGER_OVERFLOW");
+        assert!(used_amount <= amount, "ERR_AMOUNT_EXCEEDS_TRANSFER");
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_funder_deposit(sender_id, token_id, used_amount);
+        events::emit_funder_deposit(sender_id, token_id, used_amount);
+        for reward in rewards {
+            self.internal_push_reward(
+                reward.account_id.as_ref(),
+                token_id,
+                reward.amount.map(|a| a.0).unwrap_or(amount),
+                reward.memo,
+                reward.expires_at.map(|e| e.into()),
+                RewardSource::Funder(sender_id.clone()),
+                sender_id,
+            );
+        }
+        // storage of the new rewards is paid from the funder storage balance alone
+        self.internal_charge_account_storage(sender_id, initial_storage_usage);
+        amount - used_amount
+    }
+}
+
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
     /// Callback on receiving tokens by this contract.
+    /// Returns the unused amount, which the token contract sends back to `sender_id`.
     fn ft_on_transfer(
         &mut self,
         sender_id: ValidAccountId,
@@ -13,10 +76,38 @@ impl FungibleTokenReceiver for Contract {
         msg: String,
     ) -> PromiseOrValue<U128> {
         let token_in = env::predecessor_account_id();
-        assert!(msg.is_empty(), "ERR_MSG_INCORRECT");
-        assert!(token_in == self.token, "ERR_TOKEN_IS_NOT_PARAS");
-        self.internal_deposit(amount.into());
-        log!("Deposited amount : {}", self.deposited_amount);
-        PromiseOrValue::Value(U128(0))
+        self.assert_not_paused(Operation::Deposit);
+        assert!(self.whitelisted_tokens.contains(&token_in), "ERR_TOKEN_NOT_WHITELISTED");
+        if msg.is_empty() {
+            self.internal_deposit(&token_in, amount.into());
+            events::emit_deposit(&token_in, sender_id.as_ref(), amount.into());
+            return PromiseOrValue::Value(U128(0));
+        }
+        let message: TokenReceiverMessage = serde_json::from_str(&msg).expect("ERR_MSG_INCORRECT");
+        let unused_amount = match message {
+            TokenReceiverMessage::Campaign(campaign_id) => {
+                self.internal_fund_campaign(campaign_id, &token_in, amount.into());
+                events::emit_fund_campaign(campaign_id, &token_in, sender_id.as_ref(), amount.into());
+                0
+            }
+            TokenReceiverMessage::FunderDeposit {} => {
+                assert!(self.internal_has_role(sender_id.as_ref(), Role::Funder), "ERR_NOT_FUNDER");
+                let initial_storage_usage = env::storage_usage();
+                self.internal_funder_deposit(sender_id.as_ref(), &token_in, amount.into());
+                events::emit_funder_deposit(sender_id.as_ref(), &token_in, amount.into());
+                self.internal_charge_account_storage(sender_id.as_ref(), initial_storage_usage);
+                0
+            }
+            TokenReceiverMessage::Reward(reward) => {
+                assert!(self.internal_has_role(sender_id.as_ref(), Role::Funder), "ERR_NOT_FUNDER");
+                self.internal_push_funded_rewards(&token_in, sender_id.as_ref(), amount.into(), vec![reward])
+            }
+            TokenReceiverMessage::Batch(rewards) => {
+                assert!(self.internal_has_role(sender_id.as_ref(), Role::Funder), "ERR_NOT_FUNDER");
+                assert!(rewards.iter().all(|reward| reward.amount.is_some()), "ERR_MISSING_AMOUNT");
+                self.internal_push_funded_rewards(&token_in, sender_id.as_ref(), amount.into(), rewards)
+            }
+        };
+        PromiseOrValue::Value(U128(unused_amount))
     }
 }
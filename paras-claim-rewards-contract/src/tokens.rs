@@ -0,0 +1,24 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn whitelist_token(&mut self, token_id: ValidAccountId) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        assert!(self.whitelisted_tokens.insert(token_id.as_ref()), "ERR_TOKEN_ALREADY_WHITELISTED");
+    }
+
+    /// Stops new deposits and pushes of `token_id`. Existing rewards stay claimable.
+    #[payable]
+    pub fn remove_whitelisted_token(&mut self, token_id: ValidAccountId) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        assert!(token_id.as_ref() != &self.token, "ERR_CANNOT_REMOVE_DEFAULT_TOKEN");
+        assert!(self.whitelisted_tokens.remove(token_id.as_ref()), "ERR_TOKEN_NOT_WHITELISTED");
+    }
+
+    pub fn get_whitelisted_tokens(&self) -> Vec<AccountId> {
+        self.whitelisted_tokens.to_vec()
+    }
+}
@@ -0,0 +1,40 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::Gas;
+
+const STAGED_CODE_KEY: &[u8] = b"STAGED_CODE";
+
+/// Gas attached to the `migrate` call chained after the deployment.
+const GAS_FOR_MIGRATE: Gas = 50_000_000_000_000;
+
+#[near_bindgen]
+impl Contract {
+    /// Stores new contract code, passed as the raw call input instead of JSON.
+    /// Returns the sha256 hash of the code that has to be passed to `deploy_staged`.
+    pub fn stage_code(&mut self) -> Base64VecU8 {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        let code = env::input().expect("ERR_NO_CODE");
+        assert!(!code.is_empty(), "ERR_NO_CODE");
+        env::storage_write(STAGED_CODE_KEY, &code);
+        env::sha256(&code).into()
+    }
+
+    /// Deploys the staged code and migrates the state.
+    /// `code_hash` has to match the staged code, so the owner confirms what is being deployed.
+    #[payable]
+    pub fn deploy_staged(&mut self, code_hash: Base64VecU8) -> Promise {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        let code = env::storage_read(STAGED_CODE_KEY).expect("ERR_NO_STAGED_CODE");
+        assert_eq!(env::sha256(&code), code_hash.0, "ERR_CODE_HASH_MISMATCH");
+        env::storage_remove(STAGED_CODE_KEY);
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), vec![], 0, GAS_FOR_MIGRATE)
+    }
+
+    pub fn get_staged_code_hash(&self) -> Option<Base64VecU8> {
+        env::storage_read(STAGED_CODE_KEY).map(|code| env::sha256(&code).into())
+    }
+}
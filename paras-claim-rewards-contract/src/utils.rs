@@ -1,11 +1,22 @@
 use near_sdk::{ext_contract, Gas};
-use near_sdk::json_types::{U128};
+use near_sdk::json_types::{U128, U64};
+use crate::rewards::ExpiringAmount;
 
 pub const GAS_FOR_FT_TRANSFER: Gas = 10_000_000_000_000;
+pub const GAS_FOR_FT_TRANSFER_CALL: Gas = 50_000_000_000_000;
+/// Gas for claim_reward_call itself, on top of the transfer and its callback.
+pub const GAS_FOR_CLAIM_REWARD_CALL: Gas = 20_000_000_000_000;
+pub const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
+pub const GAS_FOR_FT_BALANCE_OF: Gas = 5_000_000_000_000;
+pub const GAS_FOR_PUSH_REWARD: Gas = 5_000_000_000_000;
+
+/// Keeps a single push_rewards_batch call well below the 300 TGas transaction limit.
+pub const MAX_REWARDS_PER_BATCH: u64 = 50;
 
 #[ext_contract(ext_fungible_token)]
 pub trait FungibleToken {
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+    fn ft_transfer_call(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>, msg: String);
     fn ft_balance_of(&self, account_id: AccountId);
 }
 
@@ -14,4 +25,49 @@ pub trait Vesting {
     fn callback_revoke(
         &mut self,
     );
+    fn on_claim_resolved(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        claim_index: u64,
+        used_expiring_amounts: Vec<ExpiringAmount>,
+    ) -> U128;
+    fn on_claim_call_resolved(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        claim_index: u64,
+        used_expiring_amounts: Vec<ExpiringAmount>,
+    ) -> U128;
+    fn on_airdrop_claim_resolved(
+        &mut self,
+        account_id: AccountId,
+        airdrop_id: u64,
+        index: u64,
+        amount: U128,
+    ) -> U128;
+    fn on_voucher_claim_resolved(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        nonce: U64,
+    ) -> U128;
+    fn on_audit(
+        &self,
+        token_id: AccountId,
+    ) -> bool;
+    fn on_withdraw_deposit_resolved(
+        &mut self,
+        token_id: AccountId,
+        amount: U128,
+    ) -> U128;
+    fn on_funder_withdraw_resolved(
+        &mut self,
+        funder_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+    ) -> U128;
 }
\ No newline at end of file
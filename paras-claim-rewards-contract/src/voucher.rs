@@ -0,0 +1,136 @@
+use crate::*;
+use near_sdk::json_types::{Base58PublicKey, Base64VecU8};
+use near_sdk::serde::{Deserialize, Serialize};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use std::convert::TryFrom;
+
+/// Reward signed off-chain by the voucher signer, redeemable once by `account_id`.
+#[derive(Deserialize, Serialize)]
+pub struct Voucher {
+    pub account_id: ValidAccountId,
+    pub token_id: Option<ValidAccountId>,
+    pub amount: U128,
+    /// Any voucher of `account_id` can be redeemed once per nonce, in any order.
+    pub nonce: U64,
+}
+
+impl Voucher {
+    /// Signed bytes: `borsh(contract_id: String, account_id: String, token_id: String, amount: u128, nonce: u64)`.
+    pub fn to_message(&self, contract_id: &AccountId, token_id: &AccountId) -> Vec<u8> {
+        (
+            contract_id.clone(),
+            self.account_id.as_ref().to_string(),
+            token_id.clone(),
+            u128::from(self.amount),
+            u64::from(self.nonce),
+        )
+            .try_to_vec()
+            .unwrap()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets the ed25519 key vouchers must be signed with. `None` disables vouchers.
+    #[payable]
+    pub fn set_voucher_signer(&mut self, public_key: Option<Base58PublicKey>) {
+        assert_eq!(self.owner, env::predecessor_account_id(), "ERR_NOT_OWNER");
+        assert_one_yocto();
+        self.voucher_signer = public_key.map(|public_key| {
+            // first byte is the curve type, 0 for ed25519
+            assert!(public_key.0.len() == 33 && public_key.0[0] == 0, "ERR_INVALID_PUBLIC_KEY");
+            public_key.0[1..].to_vec()
+        });
+    }
+
+    /// Redeems a voucher signed by the voucher signer, paid from the deposit.
+    /// The storage marking the voucher used is paid by the attached deposit (above the 1 yoctoNEAR),
+    /// then the caller storage balance, then the owner storage pool.
+    #[payable]
+    pub fn claim_voucher(&mut self, voucher: Voucher, signature: Base64VecU8) -> Promise {
+        assert!(env::attached_deposit() >= 1, "ERR_REQUIRES_AT_LEAST_ONE_YOCTO");
+        self.assert_not_paused(Operation::Claim);
+        let account_id = env::predecessor_account_id();
+        assert_eq!(voucher.account_id.as_ref(), &account_id, "ERR_NOT_VOUCHER_RECEIVER");
+        let signer = self.voucher_signer.as_ref().expect("ERR_NO_VOUCHER_SIGNER");
+        let token_id = self.internal_whitelisted_token_id(voucher.token_id.clone());
+        let amount: u128 = voucher.amount.into();
+        assert!(amount > 0, "ERR_AMOUNT_ZERO");
+
+        let public_key = PublicKey::from_bytes(signer).expect("ERR_INVALID_PUBLIC_KEY");
+        let signature = Signature::try_from(&signature.0[..]).expect("ERR_INVALID_SIGNATURE");
+        assert!(
+            public_key.verify(&voucher.to_message(&env::current_account_id(), &token_id), &signature).is_ok(),
+            "ERR_INVALID_SIGNATURE"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        assert!(self.used_vouchers.insert(&(account_id.clone(), voucher.nonce.into())), "ERR_VOUCHER_ALREADY_USED");
+        self.internal_withdraw_deposit(&token_id, amount);
+        self.internal_update_stats(&token_id, |stats| {
+            stats.push(amount);
+            stats.claim(amount);
+        });
+        self.internal_charge_storage(Some(&account_id), initial_storage_usage);
+
+        events::emit_claim_reward(&account_id, &token_id, amount);
+
+        ext_fungible_token::ft_transfer(
+            account_id.clone(),
+            amount.into(),
+            None,
+            &token_id,
+            1,
+            GAS_FOR_FT_TRANSFER
+        ).then(ext_self::on_voucher_claim_resolved(
+            account_id,
+            token_id,
+            amount.into(),
+            voucher.nonce,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ))
+    }
+
+    /// Returns the amount to the deposit if `ft_transfer` failed, and frees the nonce so the voucher can be redeemed again.
+    #[private]
+    pub fn on_voucher_claim_resolved(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        nonce: U64,
+    ) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit_claim_reward_resolved(&account_id, &token_id, amount.into(), true);
+                amount
+            }
+            PromiseResult::Failed => {
+                self.internal_deposit(&token_id, amount.into());
+                self.internal_update_stats(&token_id, |stats| {
+                    stats.refund_claim(amount.into());
+                    stats.cancel_push(amount.into());
+                });
+                self.used_vouchers.remove(&(account_id.clone(), nonce.into()));
+                events::emit_claim_reward_resolved(&account_id, &token_id, amount.into(), false);
+                U128(0)
+            }
+        }
+    }
+
+    pub fn get_voucher_signer(&self) -> Option<Base58PublicKey> {
+        self.voucher_signer.as_ref().map(|key| {
+            let mut bytes = vec![0];
+            bytes.extend(key.iter());
+            Base58PublicKey(bytes)
+        })
+    }
+
+    pub fn is_voucher_used(&self, account_id: ValidAccountId, nonce: U64) -> bool {
+        self.used_vouchers.contains(&(account_id.into(), nonce.into()))
+    }
+}
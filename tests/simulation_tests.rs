@@ -1,10 +1,10 @@
-use near_sdk::json_types::U128;
+use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::{AccountId};
 use near_sdk::serde_json::json;
 use near_sdk::serde_json;
 use near_sdk_sim::{call, to_yocto, transaction::ExecutionStatus, view, DEFAULT_GAS, UserAccount};
 
-use crate::utils::{init, ptoy};
+use crate::utils::{init, init_v1, ptoy, register_claimer, stage_claim_code, upgrade_claim, CLAIM_ID};
 mod utils;
 
 #[test]
@@ -64,15 +64,21 @@ fn simulate_push_reward() {
     let initial_storage_usage = claim_account.storage_usage;
 
     // assert user1 is not on the rewards record
-    let user1_reward_outcome = view!(claim.get_reward_amount(user1.valid_account_id()));
-    assert_eq!(true, user1_reward_outcome.is_err());
+    let user1_has_rewards: bool = view!(claim.has_rewards(user1.valid_account_id())).unwrap_json();
+    assert!(!user1_has_rewards);
+    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id(), None)).unwrap_json();
+    assert_eq!(user1_reward, U128(0));
 
     let outcome = call!(
         root,
         claim.push_reward(
             user1.valid_account_id(),
             U128::from(ptoy(10)),
-            "".to_string()
+            "".to_string(),
+            None,
+            None,
+            None,
+            None
         ),
         deposit = 1
     );
@@ -94,7 +100,11 @@ fn simulate_push_reward() {
         claim.push_reward(
             user1.valid_account_id(),
             U128::from(ptoy(10)),
-            "".to_string()
+            "".to_string(),
+            None,
+            None,
+            None,
+            None
         ),
         deposit = 1
     );
@@ -105,11 +115,46 @@ fn simulate_push_reward() {
     println!("[PUSH REWARD] Gas burnt for adding reward: {} TeraGas ", outcome.gas_burnt() as f64 / 1e12);
 
     // assert reward
-    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id())).unwrap_json();
+    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id(), None)).unwrap_json();
     assert_eq!(user1_reward, U128::from(ptoy(20)));
 
 }
 
+#[test]
+fn simulate_push_rewards_batch() {
+    let (root, ft, claim, alice, user1) = init();
+
+    // Deposit amount first
+
+    root.call(
+        ft.account_id(),
+        "ft_transfer_call",
+        &json!({
+            "receiver_id": claim.valid_account_id(),
+            "amount": U128::from(ptoy(10_000_000)),
+            "msg": "".to_string(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    );
+
+    let outcome = call!(
+        root,
+        claim.push_rewards_batch(vec![
+            (user1.valid_account_id(), U128::from(ptoy(10)), "".to_string()),
+            (alice.valid_account_id(), U128::from(ptoy(20)), "".to_string()),
+        ], None, None),
+        deposit = 1
+    );
+    outcome.assert_success();
+    println!("[PUSH REWARDS BATCH] Gas burnt for 2 accounts: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
+
+    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id(), None)).unwrap_json();
+    assert_eq!(user1_reward, U128::from(ptoy(10)));
+    let alice_reward: U128 = view!(claim.get_reward_amount(alice.valid_account_id(), None)).unwrap_json();
+    assert_eq!(alice_reward, U128::from(ptoy(20)));
+}
+
 #[test]
 fn simulate_claim_reward_full() {
     let (root, ft, claim, _, user1) = init();
@@ -134,13 +179,17 @@ fn simulate_claim_reward_full() {
         claim.push_reward(
             user1.valid_account_id(),
             U128::from(ptoy(10)),
-            "".to_string()
+            "".to_string(),
+            None,
+            None,
+            None,
+            None
         ),
         deposit = 1
     );
     // user1 look at his reward
 
-    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id())).unwrap_json();
+    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id(), None)).unwrap_json();
     assert_eq!(user1_reward, U128::from(ptoy(10)));
 
     let user1_balance_before: U128 = root.view(
@@ -154,9 +203,10 @@ fn simulate_claim_reward_full() {
 
     // user1 claim all reward
 
+    register_claimer(&user1);
     let outcome = call!(
         user1,
-        claim.claim_reward(user1_reward),
+        claim.claim_reward(user1_reward, None),
         deposit = 1
     );
 
@@ -164,7 +214,7 @@ fn simulate_claim_reward_full() {
     println!("[CLAIM REWARD] Gas burnt for claim reward: {} TeraGas ", outcome.gas_burnt() as f64 / 1e12);
 
     // assert user1 reward is 0
-    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id())).unwrap_json();
+    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id(), None)).unwrap_json();
     assert_eq!(user1_reward, U128::from(ptoy(0)));
 
     // assert user1 reward is sent to user1
@@ -182,6 +232,54 @@ fn simulate_claim_reward_full() {
     assert_eq!(user1_balance - user1_balance_before, ptoy(10));
 }
 
+#[test]
+fn simulate_withdraw_deposit() {
+    let (root, ft, claim, _, _) = init();
+
+    // Deposit amount first
+
+    root.call(
+        ft.account_id(),
+        "ft_transfer_call",
+        &json!({
+            "receiver_id": claim.valid_account_id(),
+            "amount": U128::from(ptoy(10_000_000)),
+            "msg": "".to_string(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    );
+
+    let root_balance_before: U128 = root.view(
+        ft.account_id(),
+        "ft_balance_of",
+        &json!({
+            "account_id": root.account_id(),
+        }).to_string().into_bytes()
+    )
+    .unwrap_json();
+
+    let outcome = call!(
+        root,
+        claim.withdraw_deposit(U128::from(ptoy(1_000)), root.valid_account_id(), None),
+        deposit = 1
+    );
+    outcome.assert_success();
+
+    let root_balance: U128 = root.view(
+        ft.account_id(),
+        "ft_balance_of",
+        &json!({
+            "account_id": root.account_id(),
+        }).to_string().into_bytes()
+    )
+    .unwrap_json();
+
+    let root_balance: u128 = root_balance.into();
+    let root_balance_before: u128 = root_balance_before.into();
+    assert_eq!(root_balance - root_balance_before, ptoy(1_000));
+}
+
 // NEGATIVE
 #[test]
 fn simulate_push_reward_invalid_account() {
@@ -206,7 +304,11 @@ fn simulate_push_reward_invalid_account() {
         claim.push_reward(
             user1.valid_account_id(),
             U128::from(ptoy(10)),
-            "".to_string()
+            "".to_string(),
+            None,
+            None,
+            None,
+            None
         ),
         deposit = 1
     );
@@ -214,7 +316,7 @@ fn simulate_push_reward_invalid_account() {
     assert_eq!(outcome.promise_errors().len(), 1);
 
     assert!(format!("{:?}", outcome.promise_errors().remove(0))
-        .contains("assertion failed: `(left == right)"));
+        .contains("ERR_NOT_DISTRIBUTOR"));
 
 }
 
@@ -242,13 +344,17 @@ fn simulate_claim_to_non_registered_user() {
         claim.push_reward(
             alice.valid_account_id(),
             U128::from(ptoy(10)),
-            "".to_string()
+            "".to_string(),
+            None,
+            None,
+            None,
+            None
         ),
         deposit = 1
     );
     // alice look at his reward
 
-    let alice_reward: U128 = view!(claim.get_reward_amount(alice.valid_account_id())).unwrap_json();
+    let alice_reward: U128 = view!(claim.get_reward_amount(alice.valid_account_id(), None)).unwrap_json();
     assert_eq!(alice_reward, U128::from(ptoy(10)));
 
     let alice_balance_before: U128 = root.view(
@@ -262,17 +368,18 @@ fn simulate_claim_to_non_registered_user() {
 
     // user1 claim all reward
 
+    register_claimer(&alice);
     let outcome = call!(
         alice,
-        claim.claim_reward(alice_reward),
+        claim.claim_reward(alice_reward, None),
         deposit = 1
     );
 
     println!("[CLAIM REWARD FAIL] Gas burnt for failed claim reward: {} TeraGas ", outcome.gas_burnt() as f64 / 1e12);
 
-    // assert alice reward is 0 (FAIL still reduce amount)
-    let user1_reward: U128 = view!(claim.get_reward_amount(alice.valid_account_id())).unwrap_json();
-    assert_eq!(user1_reward, U128::from(ptoy(0)));
+    // assert alice reward is refunded after the failed transfer
+    let alice_reward: U128 = view!(claim.get_reward_amount(alice.valid_account_id(), None)).unwrap_json();
+    assert_eq!(alice_reward, U128::from(ptoy(10)));
 
     // assert alice reward is not sent to alice
     let alice_balance: U128 = root.view(
@@ -288,4 +395,232 @@ fn simulate_claim_to_non_registered_user() {
     let alice_balance_before: u128 = alice_balance_before.into();
     assert_eq!(alice_balance, alice_balance_before);
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn simulate_claim_reward_paused() {
+    let (root, ft, claim, _, user1) = init();
+
+    // Deposit amount first
+
+    root.call(
+        ft.account_id(),
+        "ft_transfer_call",
+        &json!({
+            "receiver_id": claim.valid_account_id(),
+            "amount": U128::from(ptoy(10_000_000)),
+            "msg": "".to_string(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    );
+
+    call!(
+        root,
+        claim.push_reward(
+            user1.valid_account_id(),
+            U128::from(ptoy(10)),
+            "".to_string(),
+            None,
+            None,
+            None,
+            None
+        ),
+        deposit = 1
+    ).assert_success();
+
+    call!(
+        root,
+        claim.pause(None),
+        deposit = 1
+    ).assert_success();
+    let is_paused: bool = view!(claim.is_paused(None)).unwrap_json();
+    assert!(is_paused);
+
+    register_claimer(&user1);
+    let outcome = call!(
+        user1,
+        claim.claim_reward(U128::from(ptoy(10)), None),
+        deposit = 1
+    );
+    assert_eq!(outcome.promise_errors().len(), 1);
+    assert!(format!("{:?}", outcome.promise_errors().remove(0))
+        .contains("ERR_PAUSED"));
+
+    call!(
+        root,
+        claim.unpause(None),
+        deposit = 1
+    ).assert_success();
+    call!(
+        user1,
+        claim.claim_reward(U128::from(ptoy(10)), None),
+        deposit = 1
+    ).assert_success();
+
+    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id(), None)).unwrap_json();
+    assert_eq!(user1_reward, U128::from(ptoy(0)));
+}
+
+#[test]
+fn simulate_migrate_from_v1() {
+    let (root, ft, claim, alice, user1) = init_v1();
+
+    // Deposit amount first
+
+    root.call(
+        ft.account_id(),
+        "ft_transfer_call",
+        &json!({
+            "receiver_id": claim.valid_account_id(),
+            "amount": U128::from(ptoy(10_000_000)),
+            "msg": "".to_string(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    );
+
+    // push rewards with the V1 interface
+    for (account, amount, memo) in vec![(&user1, 10, "first"), (&user1, 20, "second"), (&alice, 30, "first")] {
+        root.call(
+            CLAIM_ID.to_string(),
+            "push_reward",
+            &json!({
+                "account_id": account.valid_account_id(),
+                "amount": U128::from(ptoy(amount)),
+                "memo": memo,
+            }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            1
+        ).assert_success();
+    }
+
+    upgrade_claim(&claim).assert_success();
+
+    let version: String = root.view(CLAIM_ID.to_string(), "get_version", b"{}").unwrap_json();
+    assert_eq!(version, "V2");
+
+    // every record survives the upgrade
+    let user1_reward: U128 = root.view(
+        CLAIM_ID.to_string(),
+        "get_reward_amount",
+        &json!({"account_id": user1.valid_account_id()}).to_string().into_bytes()
+    ).unwrap_json();
+    assert_eq!(user1_reward, U128::from(ptoy(30)));
+    let alice_reward: U128 = root.view(
+        CLAIM_ID.to_string(),
+        "get_reward_amount",
+        &json!({"account_id": alice.valid_account_id()}).to_string().into_bytes()
+    ).unwrap_json();
+    assert_eq!(alice_reward, U128::from(ptoy(30)));
+    let user1_rewards: serde_json::Value = root.view(
+        CLAIM_ID.to_string(),
+        "get_rewards",
+        &json!({"from_index": 0, "limit": 10, "account_id": user1.valid_account_id()}).to_string().into_bytes()
+    ).unwrap_json();
+    assert_eq!(user1_rewards["total_count"], 2);
+    assert_eq!(user1_rewards["rewards"][0]["memo"], "second");
+    assert_eq!(user1_rewards["rewards"][0]["amount"], U128::from(ptoy(20)).0.to_string());
+    assert_eq!(user1_rewards["rewards"][1]["memo"], "first");
+
+    // upgraded records can be claimed and pushed to
+    root.call(
+        CLAIM_ID.to_string(),
+        "storage_deposit",
+        &json!({}).to_string().into_bytes(),
+        DEFAULT_GAS / 2,
+        to_yocto("1"),
+    ).assert_success();
+    root.call(
+        CLAIM_ID.to_string(),
+        "push_reward",
+        &json!({
+            "account_id": alice.valid_account_id(),
+            "amount": U128::from(ptoy(5)),
+            "memo": "after upgrade",
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    ).assert_success();
+    let alice_rewards: serde_json::Value = root.view(
+        CLAIM_ID.to_string(),
+        "get_rewards",
+        &json!({"from_index": 0, "limit": 10, "account_id": alice.valid_account_id()}).to_string().into_bytes()
+    ).unwrap_json();
+    assert_eq!(alice_rewards["total_count"], 2);
+    assert_eq!(alice_rewards["rewards"][0]["memo"], "after upgrade");
+    assert_eq!(alice_rewards["rewards"][1]["memo"], "first");
+
+    register_claimer(&user1);
+    user1.call(
+        CLAIM_ID.to_string(),
+        "claim_reward",
+        &json!({"amount": U128::from(ptoy(30))}).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    ).assert_success();
+    let user1_balance: U128 = root.view(
+        ft.account_id(),
+        "ft_balance_of",
+        &json!({
+            "account_id": user1.account_id(),
+        }).to_string().into_bytes()
+    )
+    .unwrap_json();
+    assert_eq!(user1_balance, U128::from(ptoy(30)));
+}
+
+#[test]
+fn simulate_owner_upgrade() {
+    let (root, ft, claim, _, user1) = init();
+
+    // Deposit amount first
+
+    root.call(
+        ft.account_id(),
+        "ft_transfer_call",
+        &json!({
+            "receiver_id": claim.valid_account_id(),
+            "amount": U128::from(ptoy(10_000_000)),
+            "msg": "".to_string(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    );
+
+    call!(
+        root,
+        claim.push_reward(
+            user1.valid_account_id(),
+            U128::from(ptoy(10)),
+            "".to_string(),
+            None,
+            None,
+            None,
+            None
+        ),
+        deposit = 1
+    ).assert_success();
+
+    // only the owner can stage code
+    let outcome = user1.call(CLAIM_ID.to_string(), "stage_code", b"code", DEFAULT_GAS, 0);
+    assert!(format!("{:?}", outcome.promise_errors().remove(0))
+        .contains("ERR_NOT_OWNER"));
+
+    let outcome = stage_claim_code(&root);
+    outcome.assert_success();
+    let code_hash: Base64VecU8 = outcome.unwrap_json();
+
+    let outcome = call!(
+        root,
+        claim.deploy_staged(code_hash),
+        deposit = 1
+    );
+    outcome.assert_success();
+    println!("[UPGRADE] Gas burnt for deploy staged: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
+
+    let version: String = view!(claim.get_version()).unwrap_json();
+    assert_eq!(version, "V2");
+    let user1_reward: U128 = view!(claim.get_reward_amount(user1.valid_account_id(), None)).unwrap_json();
+    assert_eq!(user1_reward, U128::from(ptoy(10)));
+}
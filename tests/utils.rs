@@ -2,13 +2,15 @@ use paras_claim_rewards_contract::ContractContract as ClaimContract;
 use near_sdk::json_types::U128;
 use near_sdk::serde_json::json;
 use near_sdk_sim::{
-    deploy, init_simulator, to_yocto, ContractAccount, UserAccount, DEFAULT_GAS, STORAGE_AMOUNT,
+    deploy, init_simulator, to_yocto, ContractAccount, ExecutionResult, UserAccount, DEFAULT_GAS,
+    STORAGE_AMOUNT,
 };
 
 // Load in contract bytes at runtime
 near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
     FT_WASM_BYTES => "res/fungible_token.wasm",
     CLAIM_WASM_BYTES => "res/paras_claim_rewards_contract.wasm",
+    CLAIM_V1_WASM_BYTES => "res/paras_claim_rewards_contract_v1.wasm",
 }
 
 pub const FT_ID: &str = "ft";
@@ -35,10 +37,19 @@ pub fn register_user(user: &near_sdk_sim::UserAccount) {
     .assert_success();
 }
 
-pub fn init() -> (UserAccount, UserAccount, ContractAccount<ClaimContract>, UserAccount, UserAccount) {
-    // Use `None` for default genesis configuration; more info below
-    let root = init_simulator(None);
+/// Registers `user` with the claim contract, claims are paid from the claimer storage balance.
+pub fn register_claimer(user: &UserAccount) {
+    user.call(
+        CLAIM_ID.to_string(),
+        "storage_deposit",
+        &json!({}).to_string().into_bytes(),
+        DEFAULT_GAS / 2,
+        to_yocto("0.01"),
+    )
+    .assert_success();
+}
 
+fn deploy_ft(root: &UserAccount) -> UserAccount {
     let ft = root.deploy(
         &FT_WASM_BYTES,
         FT_ID.to_string(),
@@ -57,6 +68,14 @@ pub fn init() -> (UserAccount, UserAccount, ContractAccount<ClaimContract>, User
         0,
     )
     .assert_success();
+    ft
+}
+
+pub fn init() -> (UserAccount, UserAccount, ContractAccount<ClaimContract>, UserAccount, UserAccount) {
+    // Use `None` for default genesis configuration; more info below
+    let root = init_simulator(None);
+
+    let ft = deploy_ft(&root);
 
     let claim = deploy!(
         contract: ClaimContract,
@@ -71,6 +90,57 @@ pub fn init() -> (UserAccount, UserAccount, ContractAccount<ClaimContract>, User
 
     register_user(&claim.user_account);
 
+    // owner storage pool paying for pushed reward records
+    root.call(
+        CLAIM_ID.to_string(),
+        "storage_deposit",
+        &json!({}).to_string().into_bytes(),
+        DEFAULT_GAS / 2,
+        to_yocto("1"),
+    )
+    .assert_success();
+
+    let alice = root.create_user(
+        "alice".to_string(),
+        to_yocto("100") // initial balance
+    );
+
+    let user1 = root.create_user(
+        USER1_ID.into(),
+        to_yocto("100")
+    );
+    register_user(&user1);
+
+    (root, ft, claim, alice, user1)
+}
+
+/// Same as `init`, but deploys the V1 (pre-migration) build of the claim contract.
+pub fn init_v1() -> (UserAccount, UserAccount, UserAccount, UserAccount, UserAccount) {
+    let root = init_simulator(None);
+
+    let ft = deploy_ft(&root);
+
+    let claim = root.deploy(
+        &CLAIM_V1_WASM_BYTES,
+        CLAIM_ID.to_string(),
+        STORAGE_AMOUNT, // attached deposit
+    );
+    claim.call(
+        CLAIM_ID.into(),
+        "new",
+        &json!({
+            "owner": root.valid_account_id(),
+            "token": ft.valid_account_id(),
+        })
+        .to_string()
+        .into_bytes(),
+        DEFAULT_GAS / 2,
+        0,
+    )
+    .assert_success();
+
+    register_user(&claim);
+
     let alice = root.create_user(
         "alice".to_string(),
         to_yocto("100") // initial balance
@@ -83,4 +153,24 @@ pub fn init() -> (UserAccount, UserAccount, ContractAccount<ClaimContract>, User
     register_user(&user1);
 
     (root, ft, claim, alice, user1)
-}
\ No newline at end of file
+}
+
+/// Deploys the current build of the claim contract over `claim` and migrates its state.
+pub fn upgrade_claim(claim: &UserAccount) -> ExecutionResult {
+    claim
+        .create_transaction(CLAIM_ID.to_string())
+        .deploy_contract(CLAIM_WASM_BYTES.to_vec())
+        .function_call("migrate".to_string(), b"{}".to_vec(), DEFAULT_GAS, 0)
+        .submit()
+}
+
+/// Stages the current build of the claim contract as the owner. Returns the code hash.
+pub fn stage_claim_code(root: &UserAccount) -> ExecutionResult {
+    root.call(
+        CLAIM_ID.to_string(),
+        "stage_code",
+        &CLAIM_WASM_BYTES,
+        DEFAULT_GAS,
+        0,
+    )
+}